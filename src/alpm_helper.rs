@@ -1,4 +1,4 @@
-use crate::{pkg_utils, utils};
+use crate::{config, download, pkg_utils, utils};
 
 use std::path::Path;
 use std::{env, fs};
@@ -37,9 +37,14 @@ fn init_alpm(pacman_path: &str, repo_list: &[RepoData]) -> Result<Alpm> {
 fn init_profile_repo(repo_filepath: &str) -> Result<Alpm> {
     let temp_dir = utils::create_temporary_directory(None).expect("Failed to create temp dir");
 
-    let repo_dir =
-        Path::new(repo_filepath).parent().expect("Failed to get parent dir from repo filepath");
-    let repo_url = format!("file://{}", repo_dir.to_str().unwrap());
+    // remote repo is served from the parent URL, e.g 'https://example.org/repo/x86_64'
+    let repo_url = if download::is_remote_url(repo_filepath) {
+        repo_filepath.rsplit_once('/').unwrap().0.to_owned()
+    } else {
+        let repo_dir =
+            Path::new(repo_filepath).parent().expect("Failed to get parent dir from repo filepath");
+        format!("file://{}", repo_dir.to_str().unwrap())
+    };
 
     let repo_db_prefix = pkg_utils::get_repo_db_prefix(repo_filepath);
 
@@ -140,8 +145,16 @@ pub fn get_stale_packages(repo_db_path: &str) -> Result<Vec<String>> {
     Ok(stale_pkgs)
 }
 
-// Gets filenames of stale packages from the repo DB
-pub fn get_stale_filenames(repo_db_path: &str) -> Result<Vec<String>> {
+/// Package record in the repo DB
+#[derive(Debug, PartialEq, Clone)]
+pub struct DbPkg {
+    pub name: String,
+    pub version: String,
+    pub filename: String,
+}
+
+// Gets stale packages from the repo DB, with their metadata as the files don't exist
+pub fn get_stale_db_pkgs(repo_db_path: &str) -> Result<Vec<DbPkg>> {
     // we iterate through DB with alpm crate, and check for each package
     // if the package file still exist in the repo directory
     let alpm_handle =
@@ -150,7 +163,7 @@ pub fn get_stale_filenames(repo_db_path: &str) -> Result<Vec<String>> {
     let repo_dir = Path::new(&repo_db_path).parent().unwrap();
 
     // iterate through every package in the database using map iter
-    let stale_pkgs: Vec<String> = alpm_handle
+    let stale_pkgs: Vec<DbPkg> = alpm_handle
         .syncdbs()
         .iter()
         .flat_map(alpm::Db::pkgs)
@@ -161,7 +174,11 @@ pub fn get_stale_filenames(repo_db_path: &str) -> Result<Vec<String>> {
             let pkg_filepath = format!("{}/{pkg_filename}", repo_dir.to_str().unwrap());
             !Path::new(&pkg_filepath).exists()
         })
-        .map(|x| x.filename().unwrap().to_string())
+        .map(|x| DbPkg {
+            name: x.name().to_owned(),
+            version: x.version().as_str().to_owned(),
+            filename: x.filename().unwrap().to_owned(),
+        })
         .collect();
 
     // cleanup temp dir after we are done
@@ -171,13 +188,14 @@ pub fn get_stale_filenames(repo_db_path: &str) -> Result<Vec<String>> {
 }
 
 // gets packages which are not yet present in the DB
-pub fn get_brand_new_packages(repo_db_path: &str) -> Result<Vec<String>> {
+pub fn get_brand_new_packages(
+    repo_db_path: &str,
+    pkg_extensions: &[String],
+) -> Result<Vec<String>> {
     let repo_dir = Path::new(&repo_db_path).parent().unwrap();
 
     // get all local packages
-    let pkgs_list = glob::glob(&format!("{}/*.pkg.tar.zst", repo_dir.to_str().unwrap()))?
-        .map(|x| x.unwrap().to_str().unwrap().to_owned())
-        .collect::<Vec<_>>();
+    let pkgs_list = pkg_utils::find_packages_in_dir(repo_dir, pkg_extensions)?;
 
     // we iterate through DB with alpm crate, and check for each package in the list
     // if it doesn't, then we found "brand new" package (which doesn't exist yet in DB)
@@ -187,13 +205,18 @@ pub fn get_brand_new_packages(repo_db_path: &str) -> Result<Vec<String>> {
     // iterate through all files and check if they exist in the repo
     let mut new_pkgs: Vec<String> = vec![];
     for pkg_filepath in pkgs_list {
-        let pkg_filename = Path::new(&pkg_filepath).file_name().unwrap().to_str().unwrap();
-        let pkg_name = crate::pkg_utils::get_pkgname_from_filename(pkg_filename);
+        let pkginfo = match pkg_utils::read_pkg_metadata(&pkg_filepath) {
+            Ok(pkginfo) => pkginfo,
+            Err(pkg_err) => {
+                log::error!("Skipping package '{pkg_filepath}': {pkg_err:#}");
+                continue;
+            },
+        };
 
         // iterate through each database
         for db in alpm_handle.syncdbs() {
             // if package was not found, then we assume its the new package
-            if db.pkg(pkg_name).is_err() {
+            if db.pkg(pkginfo.name.as_str()).is_err() {
                 new_pkgs.push(pkg_filepath.clone());
             }
         }
@@ -205,55 +228,208 @@ pub fn get_brand_new_packages(repo_db_path: &str) -> Result<Vec<String>> {
     Ok(new_pkgs)
 }
 
-// Checks the reference repository for newer package versions and returns a list of package
-// filepaths to copy.
+/// Package found in the reference repos
+#[derive(Debug, PartialEq, Clone)]
+pub struct ReferencePkg {
+    pub name: String,
+    pub version: String,
+    /// Path of the package file, or its URL for the remote reference repo
+    pub filepath: String,
+    /// Path of the reference repo DB which provides the package
+    pub reference_repo: String,
+}
+
+// Checks the reference repositories for newer package versions and returns a list of packages
+// to copy.
 pub fn get_newer_packages_from_reference(
     repo_db_path: &str,
-    reference_repo_path: &str,
-) -> Result<Vec<String>> {
+    reference_repos: &[config::ReferenceRepo],
+) -> Result<Vec<ReferencePkg>> {
     let alpm_handle =
         init_profile_repo(repo_db_path).context("Failed to init alpm for profile repo")?;
-    let reference_alpm_handle =
-        init_profile_repo(reference_repo_path).context("Failed to init alpm for reference repo")?;
+    let reference_alpm_handles = init_reference_repos(reference_repos)?;
 
     let mut packages_to_copy = Vec::new();
 
     // Iterate over packages in the profile repository
-    for db in alpm_handle.syncdbs() {
-        for pkg in db.pkgs() {
-            // Check if the package exists in the reference repository
-            if let Some(reference_pkg) = reference_alpm_handle
-                .syncdbs()
-                .iter()
-                .find_map(|ref_db| ref_db.pkg(pkg.name()).ok())
-            {
-                // Compare versions
-                if reference_pkg.version() > pkg.version() {
-                    // Newer version found in reference repository
-                    let pkg_filename =
-                        reference_pkg.filename().expect("Invalid package doesn't have filename");
-                    let reference_repo_dir = Path::new(reference_repo_path).parent().unwrap();
-                    let pkgfile_path =
-                        format!("{}/{pkg_filename}", reference_repo_dir.to_str().unwrap());
-
-                    // skip if the package file doesn't exist in the reference repo
-                    if !Path::new(&pkgfile_path).exists() {
-                        log::error!("Package file doesn't exist in ref repo: {pkgfile_path}");
-                        continue;
-                    }
-                    packages_to_copy.push(pkgfile_path);
-                }
+    for pkg in alpm_handle.syncdbs().iter().flat_map(alpm::Db::pkgs) {
+        // Check if the package exists in the reference repositories
+        let Some((reference_repo, reference_pkg)) =
+            find_reference_pkg(reference_repos, &reference_alpm_handles, pkg.name())
+        else {
+            continue;
+        };
+
+        // Compare versions
+        if reference_pkg.version() > pkg.version() {
+            // Newer version found in reference repository
+            if let Some(reference_pkg) = get_reference_pkg(reference_repo, reference_pkg) {
+                packages_to_copy.push(reference_pkg);
             }
         }
     }
 
     // Cleanup temp dirs after we are done
     cleanup_alpm_tempdir(&alpm_handle)?;
-    cleanup_alpm_tempdir(&reference_alpm_handle)?;
+    for reference_alpm_handle in &reference_alpm_handles {
+        cleanup_alpm_tempdir(reference_alpm_handle)?;
+    }
+
+    Ok(packages_to_copy)
+}
+
+// Checks the reference repositories for packages which don't exist in the profile repository.
+// Returns a list of packages to copy
+pub fn get_new_packages_from_reference(
+    repo_db_path: &str,
+    reference_repos: &[config::ReferenceRepo],
+) -> Result<Vec<ReferencePkg>> {
+    let alpm_handle =
+        init_profile_repo(repo_db_path).context("Failed to init alpm for profile repo")?;
+    let reference_alpm_handles = init_reference_repos(reference_repos)?;
+
+    let mut packages_to_copy: Vec<ReferencePkg> = Vec::new();
+
+    // Iterate over packages in the reference repositories
+    for reference_pkg in
+        reference_alpm_handles.iter().flat_map(|x| x.syncdbs()).flat_map(alpm::Db::pkgs)
+    {
+        // Skip if the package exists in the profile repository, or it's already found
+        if alpm_handle.syncdbs().iter().any(|db| db.pkg(reference_pkg.name()).is_ok())
+            || packages_to_copy.iter().any(|x| x.name == reference_pkg.name())
+        {
+            continue;
+        }
+
+        let Some((reference_repo, reference_pkg)) =
+            find_reference_pkg(reference_repos, &reference_alpm_handles, reference_pkg.name())
+        else {
+            continue;
+        };
+        if let Some(reference_pkg) = get_reference_pkg(reference_repo, reference_pkg) {
+            packages_to_copy.push(reference_pkg);
+        }
+    }
+
+    // Cleanup temp dirs after we are done
+    cleanup_alpm_tempdir(&alpm_handle)?;
+    for reference_alpm_handle in &reference_alpm_handles {
+        cleanup_alpm_tempdir(reference_alpm_handle)?;
+    }
 
     Ok(packages_to_copy)
 }
 
+// Checks the profile repository for packages which no longer exist in the reference
+// repositories. Returns a list of package names with filepaths in the profile repository
+pub fn get_removed_packages_from_reference(
+    repo_db_path: &str,
+    reference_repos: &[config::ReferenceRepo],
+) -> Result<Vec<(String, String)>> {
+    let alpm_handle =
+        init_profile_repo(repo_db_path).context("Failed to init alpm for profile repo")?;
+    let reference_alpm_handles = init_reference_repos(reference_repos)?;
+
+    let repo_dir = Path::new(repo_db_path).parent().unwrap();
+
+    let mut removed_packages = Vec::new();
+
+    // Iterate over packages in the profile repository
+    for pkg in alpm_handle.syncdbs().iter().flat_map(alpm::Db::pkgs) {
+        // Skip if the package still exists in any of the reference repositories
+        if find_reference_pkg(reference_repos, &reference_alpm_handles, pkg.name()).is_some() {
+            continue;
+        }
+
+        let pkg_filename = pkg.filename().expect("Invalid package doesn't have filename");
+        let pkgfile_path = format!("{}/{pkg_filename}", repo_dir.to_str().unwrap());
+        removed_packages.push((pkg.name().to_owned(), pkgfile_path));
+    }
+
+    // Cleanup temp dirs after we are done
+    cleanup_alpm_tempdir(&alpm_handle)?;
+    for reference_alpm_handle in &reference_alpm_handles {
+        cleanup_alpm_tempdir(reference_alpm_handle)?;
+    }
+
+    Ok(removed_packages)
+}
+
+fn init_reference_repos(reference_repos: &[config::ReferenceRepo]) -> Result<Vec<Alpm>> {
+    let mut reference_alpm_handles = Vec::with_capacity(reference_repos.len());
+    for reference_repo in reference_repos {
+        let reference_alpm_handle = init_profile_repo(&reference_repo.path).with_context(|| {
+            format!("Failed to init alpm for reference repo '{}'", reference_repo.path)
+        })?;
+        reference_alpm_handles.push(reference_alpm_handle);
+    }
+    Ok(reference_alpm_handles)
+}
+
+// Finds the package in the reference repositories, which aren't filtering it out.
+// The highest version wins, and on equal versions the repository with the highest priority
+fn find_reference_pkg<'a>(
+    reference_repos: &'a [config::ReferenceRepo],
+    reference_alpm_handles: &'a [Alpm],
+    pkgname: &str,
+) -> Option<(&'a config::ReferenceRepo, &'a alpm::Package)> {
+    let mut found_pkg: Option<(&config::ReferenceRepo, &alpm::Package)> = None;
+    for (reference_repo, reference_alpm_handle) in
+        reference_repos.iter().zip(reference_alpm_handles)
+    {
+        if !reference_repo.is_allowed(pkgname) {
+            continue;
+        }
+        let Some(reference_pkg) =
+            reference_alpm_handle.syncdbs().iter().find_map(|ref_db| ref_db.pkg(pkgname).ok())
+        else {
+            continue;
+        };
+
+        let is_preferred = found_pkg.is_none_or(|(found_repo, found_pkg)| {
+            let version_ord = reference_pkg.version().vercmp(found_pkg.version());
+            version_ord.then(reference_repo.priority.cmp(&found_repo.priority)).is_gt()
+        });
+        if is_preferred {
+            found_pkg = Some((reference_repo, reference_pkg));
+        }
+    }
+    found_pkg
+}
+
+fn get_reference_pkg(
+    reference_repo: &config::ReferenceRepo,
+    reference_pkg: &alpm::Package,
+) -> Option<ReferencePkg> {
+    let pkg_filename = reference_pkg.filename().expect("Invalid package doesn't have filename");
+    let filepath = get_reference_pkgfile_path(&reference_repo.path, pkg_filename)?;
+    Some(ReferencePkg {
+        name: reference_pkg.name().to_owned(),
+        version: reference_pkg.version().as_str().to_owned(),
+        filepath,
+        reference_repo: reference_repo.path.clone(),
+    })
+}
+
+// Gets the path of the package file in the reference repository.
+// For the remote repo it's the URL of the package file, which is downloaded later
+fn get_reference_pkgfile_path(reference_repo_path: &str, pkg_filename: &str) -> Option<String> {
+    if download::is_remote_url(reference_repo_path) {
+        let reference_repo_url = reference_repo_path.rsplit_once('/').unwrap().0;
+        return Some(format!("{reference_repo_url}/{pkg_filename}"));
+    }
+
+    let reference_repo_dir = Path::new(reference_repo_path).parent().unwrap();
+    let pkgfile_path = format!("{}/{pkg_filename}", reference_repo_dir.to_str().unwrap());
+
+    // skip if the package file doesn't exist in the reference repo
+    if !Path::new(&pkgfile_path).exists() {
+        log::error!("Package file doesn't exist in ref repo: {pkgfile_path}");
+        return None;
+    }
+    Some(pkgfile_path)
+}
+
 fn cleanup_alpm_tempdir(alpm_handle: &Alpm) -> Result<()> {
     let tmp_dir = env::temp_dir();
 
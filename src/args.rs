@@ -1,4 +1,6 @@
-use clap::Parser;
+use crate::report::OutputFormat;
+
+use clap::{ArgAction, Parser};
 
 #[derive(Parser, PartialEq, Debug)]
 #[command(author, version, about, long_about = None)]
@@ -15,6 +17,30 @@ pub(crate) struct Cli {
     #[arg(global = true, short, long)]
     to: Option<String>,
 
+    /// Print the operations which would be made, without touching any files
+    #[arg(global = true, long)]
+    pub dry_run: bool,
+
+    /// Ask for confirmation before destructive actions (ignored if stdin is not a terminal)
+    #[arg(global = true, short, long)]
+    pub interactive: bool,
+
+    /// Format of the report (used by is-pkgs-up-to-date and show-profile)
+    #[arg(global = true, long, value_enum, default_value_t)]
+    pub output: OutputFormat,
+
+    /// Path to the config file, overrides REPO_MANAGE_CONFIG env and the default locations
+    #[arg(global = true, short, long)]
+    pub config: Option<String>,
+
+    /// Log more details, can be repeated (-vv)
+    #[arg(global = true, short, long, action = ArgAction::Count)]
+    pub verbose: u8,
+
+    /// Log only warnings and errors, can be repeated (-qq) to log only errors
+    #[arg(global = true, short, long, action = ArgAction::Count)]
+    pub quiet: u8,
+
     #[command(subcommand)]
     pub command: Commands,
 }
@@ -53,12 +79,53 @@ pub(crate) enum Commands {
     /// Cleans up the backup directory,
     /// removing the N amount of packages if configured to do so
     CleanupBackupDir(SingleProfileCli),
-    // Check if we have only certain amount of debug packages in the debug repository
-    // IsDebugPkgsOk, // ok maybe not implemented
+    /// Check if we have only certain amount of debug packages in the debug repository,
+    /// pruning the debug packages which are not kept by the retention policy
+    IsDebugPkgsOk(SingleProfileCli),
+    /// Undo the last operation on the repository,
+    /// restoring the previous DB state and moving the files back
+    Undo(SingleProfileCli),
+    /// Print the profile with the settings resolved from the defaults and inherited profiles
+    ShowProfile(SingleProfileCli),
+}
+
+impl Commands {
+    // Gets the name of the command, as it's given on the command line
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Reset(_) => "reset",
+            Self::Update(_) => "update",
+            Self::Sync(_) => "sync",
+            Self::MovePkgsToRepo(_) => "move-pkgs-to-repo",
+            Self::MovePkgs(_) => "move-pkgs",
+            Self::IsPkgsUpToDate(_) => "is-pkgs-up-to-date",
+            Self::CleanupBackupDir(_) => "cleanup-backup-dir",
+            Self::IsDebugPkgsOk(_) => "is-debug-pkgs-ok",
+            Self::Undo(_) => "undo",
+            Self::ShowProfile(_) => "show-profile",
+        }
+    }
+
+    // Gets the profile the command runs on, e.g 'repof' or 'repof->reposecond' for move-pkgs
+    pub fn profile_name(&self) -> String {
+        match self {
+            Self::MovePkgs(args) => format!("{}->{}", args.from, args.to),
+            Self::Reset(args)
+            | Self::Update(args)
+            | Self::Sync(args)
+            | Self::MovePkgsToRepo(args)
+            | Self::IsPkgsUpToDate(args)
+            | Self::CleanupBackupDir(args)
+            | Self::IsDebugPkgsOk(args)
+            | Self::Undo(args)
+            | Self::ShowProfile(args) => args.profile.clone(),
+        }
+    }
 }
 
 #[cfg(test)]
 mod tests {
+    use crate::report::OutputFormat;
     use crate::{Cli, Commands, FromToProfileCli, SingleProfileCli};
 
     use clap::Parser;
@@ -69,6 +136,12 @@ mod tests {
             profile: Some("abcd".to_owned()),
             to: None,
             from: None,
+            dry_run: false,
+            interactive: false,
+            output: OutputFormat::Text,
+            config: None,
+            verbose: 0,
+            quiet: 0,
             command: Commands::Reset(SingleProfileCli { profile: "abcd".to_owned() })
         });
 
@@ -76,16 +149,77 @@ mod tests {
             profile: Some("abcd".to_owned()),
             to: None,
             from: None,
+            dry_run: false,
+            interactive: false,
+            output: OutputFormat::Text,
+            config: None,
+            verbose: 0,
+            quiet: 0,
             command: Commands::Reset(SingleProfileCli { profile: "abcd".to_owned() })
         });
     }
 
+    #[test]
+    fn dry_run_check() {
+        assert!(Cli::parse_from(["test", "--dry-run", "reset", "--profile", "abcd"]).dry_run);
+        assert!(
+            Cli::parse_from(["test", "move-pkgs", "--to", "abcd", "--from", "dcba", "--dry-run"])
+                .dry_run
+        );
+        assert!(!Cli::parse_from(["test", "update", "--profile", "abcd"]).dry_run);
+    }
+
+    #[test]
+    fn interactive_check() {
+        assert!(
+            Cli::parse_from(["test", "--interactive", "reset", "--profile", "abcd"]).interactive
+        );
+        assert!(Cli::parse_from(["test", "cleanup-backup-dir", "-p", "abcd", "-i"]).interactive);
+        assert!(!Cli::parse_from(["test", "update", "--profile", "abcd"]).interactive);
+    }
+
+    #[test]
+    fn output_check() {
+        let cli = Cli::parse_from(["test", "is-pkgs-up-to-date", "-p", "abcd", "--output", "json"]);
+        assert_eq!(cli.output, OutputFormat::Json);
+        let cli = Cli::parse_from(["test", "--output", "yaml", "is-pkgs-up-to-date", "-p", "abcd"]);
+        assert_eq!(cli.output, OutputFormat::Yaml);
+        assert!(Cli::try_parse_from(["test", "--output", "xml", "update", "-p", "abcd"]).is_err());
+    }
+
+    #[test]
+    fn verbosity_check() {
+        let cli = Cli::parse_from(["test", "-vv", "update", "--profile", "abcd"]);
+        assert_eq!((cli.verbose, cli.quiet), (2, 0));
+        let cli = Cli::parse_from(["test", "update", "-p", "abcd", "-q", "--quiet"]);
+        assert_eq!((cli.verbose, cli.quiet), (0, 2));
+        let cli = Cli::parse_from(["test", "update", "--profile", "abcd"]);
+        assert_eq!((cli.verbose, cli.quiet), (0, 0));
+    }
+
+    #[test]
+    fn command_name_check() {
+        let cli = Cli::parse_from(["test", "is-pkgs-up-to-date", "-p", "abcd"]);
+        assert_eq!(cli.command.name(), "is-pkgs-up-to-date");
+        assert_eq!(cli.command.profile_name(), "abcd");
+
+        let cli = Cli::parse_from(["test", "move-pkgs", "--to", "abcd", "--from", "dcba"]);
+        assert_eq!(cli.command.name(), "move-pkgs");
+        assert_eq!(cli.command.profile_name(), "dcba->abcd");
+    }
+
     #[test]
     fn from_to_profile_check() {
         assert_eq!(Cli::parse_from(["test", "move-pkgs", "--to", "abcd", "--from", "dcba"]), Cli {
             profile: None,
             to: Some("abcd".to_owned()),
             from: Some("dcba".to_owned()),
+            dry_run: false,
+            interactive: false,
+            output: OutputFormat::Text,
+            config: None,
+            verbose: 0,
+            quiet: 0,
             command: Commands::MovePkgs(FromToProfileCli {
                 to: "abcd".to_owned(),
                 from: "dcba".to_owned()
@@ -96,6 +230,12 @@ mod tests {
             profile: None,
             to: Some("abcd".to_owned()),
             from: Some("dcba".to_owned()),
+            dry_run: false,
+            interactive: false,
+            output: OutputFormat::Text,
+            config: None,
+            verbose: 0,
+            quiet: 0,
             command: Commands::MovePkgs(FromToProfileCli {
                 to: "abcd".to_owned(),
                 from: "dcba".to_owned()
@@ -0,0 +1,157 @@
+use std::fs;
+use std::io::{Read, Write};
+
+use anyhow::Result;
+
+/// Compression of tar archives used for packages and repo DBs
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Compression {
+    Zstd,
+    Xz,
+    Gzip,
+    Bzip2,
+    Lz4,
+    None,
+}
+
+impl Compression {
+    // Detects compression by the extension of the archive,
+    // e.g 'repo.db.tar.zst' or 'dash-0.5.12-1.1-x86_64.pkg.tar.xz'
+    pub fn from_path(archive_path: &str) -> Option<Self> {
+        let compression = match archive_path.rsplit_once('.')?.1 {
+            "zst" => Self::Zstd,
+            "xz" => Self::Xz,
+            "gz" => Self::Gzip,
+            "bz2" => Self::Bzip2,
+            "lz4" => Self::Lz4,
+            "tar" => Self::None,
+            _ => return None,
+        };
+        Some(compression)
+    }
+}
+
+// Opens the archive for reading with decompression detected from the path
+pub fn open_decoder(archive_path: &str, archive_file: fs::File) -> Result<Box<dyn Read>> {
+    let decoder: Box<dyn Read> = match Compression::from_path(archive_path) {
+        Some(Compression::Zstd) => Box::new(zstd::Decoder::new(archive_file)?),
+        Some(Compression::Xz) => Box::new(xz2::read::XzDecoder::new(archive_file)),
+        Some(Compression::Gzip) => Box::new(flate2::read::GzDecoder::new(archive_file)),
+        Some(Compression::Bzip2) => Box::new(bzip2::read::BzDecoder::new(archive_file)),
+        Some(Compression::Lz4) => Box::new(lz4_flex::frame::FrameDecoder::new(archive_file)),
+        Some(Compression::None) => Box::new(archive_file),
+        None => anyhow::bail!("Unsupported compression of '{archive_path}'"),
+    };
+    Ok(decoder)
+}
+
+// Creates the archive writer with compression detected from the path
+pub fn create_encoder(archive_path: &str, archive_file: fs::File) -> Result<Box<dyn FinishWrite>> {
+    let encoder: Box<dyn FinishWrite> = match Compression::from_path(archive_path) {
+        Some(Compression::Zstd) => Box::new(zstd::Encoder::new(archive_file, 0)?),
+        Some(Compression::Xz) => Box::new(xz2::write::XzEncoder::new(archive_file, 6)),
+        Some(Compression::Gzip) => {
+            Box::new(flate2::write::GzEncoder::new(archive_file, flate2::Compression::default()))
+        },
+        Some(Compression::Bzip2) => {
+            Box::new(bzip2::write::BzEncoder::new(archive_file, bzip2::Compression::default()))
+        },
+        Some(Compression::Lz4) => Box::new(lz4_flex::frame::FrameEncoder::new(archive_file)),
+        Some(Compression::None) => Box::new(archive_file),
+        None => anyhow::bail!("Unsupported compression of '{archive_path}'"),
+    };
+    Ok(encoder)
+}
+
+/// Writer which has to be finalized after all data is written (e.g compressor)
+pub trait FinishWrite: Write {
+    fn finish(self: Box<Self>) -> Result<()>;
+}
+
+impl FinishWrite for fs::File {
+    fn finish(self: Box<Self>) -> Result<()> {
+        self.sync_all()?;
+        Ok(())
+    }
+}
+
+impl FinishWrite for zstd::Encoder<'_, fs::File> {
+    fn finish(self: Box<Self>) -> Result<()> {
+        zstd::Encoder::finish(*self)?.sync_all()?;
+        Ok(())
+    }
+}
+
+impl FinishWrite for xz2::write::XzEncoder<fs::File> {
+    fn finish(self: Box<Self>) -> Result<()> {
+        xz2::write::XzEncoder::finish(*self)?.sync_all()?;
+        Ok(())
+    }
+}
+
+impl FinishWrite for flate2::write::GzEncoder<fs::File> {
+    fn finish(self: Box<Self>) -> Result<()> {
+        flate2::write::GzEncoder::finish(*self)?.sync_all()?;
+        Ok(())
+    }
+}
+
+impl FinishWrite for bzip2::write::BzEncoder<fs::File> {
+    fn finish(self: Box<Self>) -> Result<()> {
+        bzip2::write::BzEncoder::finish(*self)?.sync_all()?;
+        Ok(())
+    }
+}
+
+impl FinishWrite for lz4_flex::frame::FrameEncoder<fs::File> {
+    fn finish(self: Box<Self>) -> Result<()> {
+        lz4_flex::frame::FrameEncoder::finish(*self)?.sync_all()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::compression::*;
+    use crate::utils;
+
+    #[test]
+    fn test_compression_from_path() {
+        assert_eq!(Compression::from_path("repo.db.tar.zst"), Some(Compression::Zstd));
+        assert_eq!(Compression::from_path("dash-0.5-1-x86_64.pkg.tar.xz"), Some(Compression::Xz));
+        assert_eq!(Compression::from_path("dash-0.5-1-x86_64.pkg.tar.gz"), Some(Compression::Gzip));
+        assert_eq!(
+            Compression::from_path("dash-0.5-1-x86_64.pkg.tar.bz2"),
+            Some(Compression::Bzip2)
+        );
+        assert_eq!(Compression::from_path("dash-0.5-1-x86_64.pkg.tar.lz4"), Some(Compression::Lz4));
+        assert_eq!(Compression::from_path("dash-0.5-1-x86_64.pkg.tar"), Some(Compression::None));
+        assert_eq!(Compression::from_path("repo.db"), None);
+        assert_eq!(Compression::from_path("dash-0.5-1-x86_64.pkg.tar.lrz"), None);
+        assert_eq!(Compression::from_path("README"), None);
+    }
+
+    #[test]
+    fn test_roundtrip() {
+        let temp_dir = utils::create_temporary_directory(None).expect("Failed to create temp dir");
+
+        for ext in ["tar.zst", "tar.xz", "tar.gz", "tar.bz2", "tar.lz4", "tar"] {
+            let archive_path = format!("{temp_dir}/archive.{ext}");
+
+            let archive_file = fs::File::create(&archive_path).unwrap();
+            let mut encoder = create_encoder(&archive_path, archive_file).unwrap();
+            encoder.write_all(b"archive content").unwrap();
+            encoder.finish().unwrap();
+
+            let archive_file = fs::File::open(&archive_path).unwrap();
+            let mut content = String::new();
+            open_decoder(&archive_path, archive_file)
+                .unwrap()
+                .read_to_string(&mut content)
+                .unwrap();
+            assert_eq!(content, "archive content", "roundtrip of '{ext}'");
+        }
+
+        fs::remove_dir_all(temp_dir).unwrap();
+    }
+}
@@ -1,67 +1,600 @@
+use crate::pkg_utils::{self, PKG_EXTENSIONS};
+use crate::report::OutputFormat;
+use crate::{download, sig_utils};
+
 use std::collections::HashMap;
-use std::path::Path;
+use std::path::{Component, Path, PathBuf};
 use std::{env, fs};
 
-use anyhow::Result;
-use serde::Deserialize;
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+
+const SYSTEM_CONFIG_PATH: &str = "/etc/repo-manage/config.toml";
 
 #[derive(Debug, PartialEq, Default, Deserialize)]
 #[serde(default)]
 pub struct Config {
     pub profiles: HashMap<String, Profile>,
+    pub log: LogConfig,
 }
 
-#[derive(Debug, PartialEq, Default, Deserialize)]
+/// Log output of the runs
+#[derive(Debug, PartialEq, Clone, Deserialize)]
+#[serde(default)]
+pub struct LogConfig {
+    /// Format of the console log
+    pub format: LogFormat,
+    /// File to append the log to, it's rotated when it exceeds the max size
+    pub file: Option<String>,
+    pub file_format: LogFormat,
+    /// Size of the log file in bytes which triggers the rotation, no rotation if 0
+    pub max_file_size: u64,
+    /// The number of rotated log files to keep
+    pub max_files: usize,
+    /// Send the log to the systemd journal too
+    pub journald: bool,
+}
+
+impl Default for LogConfig {
+    fn default() -> Self {
+        Self {
+            format: LogFormat::Text,
+            file: None,
+            file_format: LogFormat::Json,
+            max_file_size: 10 * 1024 * 1024,
+            max_files: 5,
+            journald: false,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Default, Clone, Copy, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum LogFormat {
+    /// Log lines with the timestamp, level, profile and command
+    #[default]
+    Text,
+    /// JSON object per line
+    Json,
+}
+
+#[derive(Debug, PartialEq, Default, Clone, Deserialize, Serialize)]
 pub struct Profile {
     pub repo: String,
+    /// Profile to inherit the settings from, which are not set in this profile
+    pub inherits: Option<String>,
     #[serde(default = "default_add_params")]
     pub add_params: Vec<String>,
     #[serde(default = "default_rm_params")]
     pub rm_params: Vec<String>,
     #[serde(default = "default_require_signature")]
     pub require_signature: bool,
+    /// Keyring with trusted keys to verify package signatures against,
+    /// if not set the default keyring of gpgv is used
+    pub keyring: Option<String>,
+    /// Fingerprints of the keys which are allowed to sign packages,
+    /// any key from the keyring is accepted if empty
+    #[serde(default)]
+    pub allowed_signers: Vec<String>,
     #[serde(default = "default_backup")]
     pub backup: bool,
     pub backup_dir: Option<String>,
     /// The number of package versions to keep in the backup directory
     pub backup_num: Option<usize>,
     pub debug_dir: Option<String>,
+    /// The number of debug package versions to keep in the debug repo
+    pub debug_keep_num: Option<usize>,
+    /// Keep only debug packages which match versions of the packages in the main repo
+    #[serde(default)]
+    pub debug_keep_matching: bool,
     #[serde(default = "default_interactive")]
     pub interactive: bool,
     pub reference_repo: Option<String>,
+    /// Additional reference repos with their priorities and package name filters
+    #[serde(default)]
+    pub reference_repos: Vec<ReferenceRepo>,
+    /// The way the repo DB is updated
+    #[serde(default)]
+    pub db_backend: DbBackend,
+    /// Extensions of the package files which are accepted into the repo
+    #[serde(default = "default_pkg_extensions")]
+    pub pkg_extensions: Vec<String>,
+    /// Sync of the packages which exist only in the reference repo
+    #[serde(default)]
+    pub sync_new: SyncMode,
+    /// Sync of the packages which were removed from the reference repo
+    #[serde(default)]
+    pub sync_removed: SyncMode,
+    /// Seconds to wait for the repo lock held by another process
+    #[serde(default = "default_lock_timeout")]
+    pub lock_timeout: u64,
+}
+
+/// Opt-in sync mode with the reference repo, limited by the package name filters
+#[derive(Debug, PartialEq, Default, Clone, Deserialize, Serialize)]
+#[serde(default)]
+pub struct SyncMode {
+    pub enabled: bool,
+    /// Package name patterns (e.g 'linux-*') which are synced, all packages if empty
+    pub allow: Vec<String>,
+    /// Package name patterns which are never synced
+    pub deny: Vec<String>,
+}
+
+impl SyncMode {
+    // Checks if the package is synced with this mode
+    pub fn is_allowed(&self, pkgname: &str) -> bool {
+        self.enabled
+            && (self.allow.is_empty() || matches_any_pattern(&self.allow, pkgname))
+            && !matches_any_pattern(&self.deny, pkgname)
+    }
+}
+
+/// Reference repo to sync the packages from
+#[derive(Debug, PartialEq, Clone, Deserialize, Serialize)]
+pub struct ReferenceRepo {
+    /// Path or HTTP(S) URL of the reference repo DB
+    pub path: String,
+    /// The repo with the higher priority wins if several repos provide the same package version
+    #[serde(default)]
+    pub priority: i32,
+    /// Package name patterns which are synced from this repo, all packages if empty
+    #[serde(default)]
+    pub filter: Vec<String>,
+}
+
+impl ReferenceRepo {
+    // Checks if the package is synced from this repo
+    pub fn is_allowed(&self, pkgname: &str) -> bool {
+        self.filter.is_empty() || matches_any_pattern(&self.filter, pkgname)
+    }
+}
+
+#[derive(Debug, PartialEq, Default, Clone, Copy, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum DbBackend {
+    /// Calls repo-add/repo-remove scripts
+    #[default]
+    RepoAdd,
+    /// Writes the repo DB natively without external tools
+    Native,
+}
+
+impl Profile {
+    // Derives the profile of the separate debug repo '<repo>-debug' stored in the debug_dir.
+    // The debug repo shares the rest of the settings (e.g backup) with the main repo
+    pub fn get_debug_profile(&self) -> Option<Profile> {
+        let debug_dir = self.debug_dir.as_ref()?.trim_end_matches('/');
+        let repo_path = Path::new(&self.repo);
+        if debug_dir == self.repo || Path::new(debug_dir) == repo_path.parent()? {
+            return None;
+        }
+
+        let repo_db_filename = repo_path.file_name()?.to_str()?;
+        let repo_db_prefix = pkg_utils::get_repo_db_prefix(&self.repo);
+        let repo_db_suffix = &repo_db_filename[repo_db_prefix.len()..];
+
+        Some(Profile {
+            repo: format!("{debug_dir}/{repo_db_prefix}-debug{repo_db_suffix}"),
+            debug_dir: None,
+            reference_repo: None,
+            reference_repos: vec![],
+            ..self.clone()
+        })
+    }
+
+    // Checks if the package signatures must be verified, which is also the case when only
+    // the allowed signers are set
+    pub fn is_signature_checked(&self) -> bool {
+        self.require_signature || !self.allowed_signers.is_empty()
+    }
+
+    // Gets the dir to move the outdated packages into. None if the backup is disabled,
+    // or the backup dir is the repo dir, so the outdated packages are kept in place
+    pub fn get_backup_dir(&self) -> Option<&str> {
+        let backup_dir = self.backup_dir.as_deref().filter(|_| self.backup)?;
+        if Path::new(backup_dir) == Path::new(&self.repo).parent()? {
+            return None;
+        }
+        Some(backup_dir)
+    }
+
+    // Gets all reference repos of the profile, ordered by priority from the highest one.
+    // The single reference_repo has the default priority and no filter
+    pub fn get_reference_repos(&self) -> Vec<ReferenceRepo> {
+        let mut reference_repos = self
+            .reference_repo
+            .iter()
+            .map(|x| ReferenceRepo { path: x.clone(), priority: 0, filter: vec![] })
+            .chain(self.reference_repos.iter().cloned())
+            .collect::<Vec<_>>();
+        reference_repos.sort_by_key(|x| std::cmp::Reverse(x.priority));
+        reference_repos
+    }
+
+    // Serializes the resolved profile into the output format, the text format is TOML
+    pub fn render(&self, output_format: OutputFormat) -> Result<String> {
+        let rendered_profile = match output_format {
+            OutputFormat::Text => toml::to_string(self)?,
+            OutputFormat::Json => serde_json::to_string_pretty(self)? + "\n",
+            OutputFormat::Yaml => serde_yaml::to_string(self)?,
+        };
+        Ok(rendered_profile)
+    }
 }
 
+// Parses the config file together with the drop-in files from 'config.d' dir next to it
 pub fn parse_config_file(filepath: &str) -> Result<Config> {
-    let file_content = fs::read_to_string(filepath)?;
-    parse_config_content(&file_content)
+    let file_content = fs::read_to_string(filepath)
+        .with_context(|| format!("Failed to read config file '{filepath}'"))?;
+    if file_content.is_empty() {
+        anyhow::bail!("The config file '{filepath}' is empty!")
+    }
+    let mut config_table = parse_config_table(&file_content, filepath)?;
+
+    // remember which file defines the profile, to report the overrides
+    let mut profile_sources = HashMap::new();
+    if let Some(profiles) = config_table.get("profiles").and_then(toml::Value::as_table) {
+        profile_sources.extend(profiles.keys().map(|x| (x.clone(), filepath.to_owned())));
+    }
+
+    for dropin_path in get_dropin_paths(filepath)? {
+        let dropin_content = fs::read_to_string(&dropin_path)
+            .with_context(|| format!("Failed to read config file '{dropin_path}'"))?;
+        let dropin_table = parse_config_table(&dropin_content, &dropin_path)?;
+        merge_config_table(&mut config_table, dropin_table, &dropin_path, &mut profile_sources)?;
+    }
+
+    // the relative repo paths are resolved against the dir of the main config
+    let config_dir = std::path::absolute(filepath)?.parent().unwrap().to_path_buf();
+    parse_config_value(toml::Value::Table(config_table), &config_dir)
 }
 
-pub fn get_config_path() -> Result<String> {
-    // Search for config file in home and system directories
-    let home_env = env::var("HOME").expect("Failed to get HOME environment");
+// Gets the config path from the --config arg or REPO_MANAGE_CONFIG env,
+// otherwise searches for the config in the user and system config directories
+pub fn get_config_path(config_arg: Option<&str>) -> Result<String> {
+    let explicit_config_path =
+        config_arg.map(str::to_owned).or_else(|| get_env_var("REPO_MANAGE_CONFIG"));
+    if let Some(config_path) = explicit_config_path {
+        if !Path::new(&config_path).exists() {
+            anyhow::bail!("Config file '{config_path}' doesn't exist");
+        }
+        return Ok(config_path);
+    }
 
-    let home_config_path = format!("{home_env}/{}", ".config/repo-manage/config.toml");
+    let check_paths = get_config_search_paths(get_env_var("XDG_CONFIG_HOME"), get_env_var("HOME"));
+    for check_path in &check_paths {
+        if Path::new(check_path).exists() {
+            return Ok(check_path.clone());
+        }
+    }
 
-    let check_paths = [home_config_path, "/etc/repo-manage/config.toml".to_owned()];
-    for check_path in check_paths {
-        if !Path::new(&check_path).exists() {
-            continue;
+    anyhow::bail!("Failed to find config! Searched in: {}", check_paths.join(", "));
+}
+
+// Gets the config paths to search in order, the user config is skipped if HOME is unset
+// (e.g for the system services)
+fn get_config_search_paths(xdg_config_home: Option<String>, home: Option<String>) -> Vec<String> {
+    let user_config_dir = xdg_config_home
+        .filter(|x| Path::new(x).is_absolute())
+        .or_else(|| home.map(|x| format!("{x}/.config")));
+
+    let mut check_paths = vec![];
+    if let Some(user_config_dir) = user_config_dir {
+        check_paths.push(format!("{user_config_dir}/repo-manage/config.toml"));
+    }
+    check_paths.push(SYSTEM_CONFIG_PATH.to_owned());
+    check_paths
+}
+
+fn get_env_var(name: &str) -> Option<String> {
+    env::var(name).ok().filter(|x| !x.is_empty())
+}
+
+// Gets the drop-in files 'config.d/*.toml' next to the config, sorted by the file name
+fn get_dropin_paths(config_path: &str) -> Result<Vec<String>> {
+    let dropin_dir = Path::new(config_path).with_file_name("config.d");
+    if !dropin_dir.is_dir() {
+        return Ok(vec![]);
+    }
+
+    let mut dropin_paths = vec![];
+    for dir_entry in fs::read_dir(&dropin_dir)
+        .with_context(|| format!("Failed to read config dir '{}'", dropin_dir.display()))?
+    {
+        let dropin_path = dir_entry?.path();
+        if dropin_path.extension().is_some_and(|x| x == "toml") && dropin_path.is_file() {
+            dropin_paths.push(dropin_path.to_str().unwrap().to_owned());
         }
-        // we found config path
-        return Ok(check_path);
     }
+    dropin_paths.sort();
+    Ok(dropin_paths)
+}
+
+fn parse_config_table(file_content: &str, filepath: &str) -> Result<toml::Table> {
+    toml::from_str(file_content)
+        .with_context(|| format!("Failed to parse config file '{filepath}'"))
+}
 
-    anyhow::bail!("Failed to find config!");
+// Merges the drop-in config into the config. The profiles of the later file replace the whole
+// profiles with the same name, the keys of the other tables (e.g 'log') are merged
+fn merge_config_table(
+    config_table: &mut toml::Table,
+    dropin_table: toml::Table,
+    dropin_path: &str,
+    profile_sources: &mut HashMap<String, String>,
+) -> Result<()> {
+    for (key, value) in dropin_table {
+        if key != "profiles" {
+            match (config_table.get_mut(&key), value) {
+                (Some(toml::Value::Table(table)), toml::Value::Table(dropin_table)) => {
+                    table.extend(dropin_table);
+                },
+                (_, value) => {
+                    config_table.insert(key, value);
+                },
+            }
+            continue;
+        }
+
+        let toml::Value::Table(dropin_profiles) = value else {
+            anyhow::bail!("Invalid 'profiles' in config file '{dropin_path}', expected table");
+        };
+        let Some(profiles) = config_table
+            .entry("profiles")
+            .or_insert_with(|| toml::Value::Table(toml::Table::new()))
+            .as_table_mut()
+        else {
+            anyhow::bail!("Invalid 'profiles' in config, expected table");
+        };
+        for (profile_name, profile) in dropin_profiles {
+            if let Some(prev_path) =
+                profile_sources.insert(profile_name.clone(), dropin_path.to_owned())
+            {
+                log::info!(
+                    "Profile '{profile_name}' from '{prev_path}' is overridden by '{dropin_path}'"
+                );
+            }
+            profiles.insert(profile_name, profile);
+        }
+    }
+    Ok(())
 }
 
+// Parses the single config, without the drop-in files
+#[cfg(test)]
 fn parse_config_content(file_content: &str) -> Result<Config> {
     if file_content.is_empty() {
         anyhow::bail!("The config file is empty!")
     }
-    let config: Config = toml::from_str(file_content)?;
+    parse_config_value(toml::Value::Table(toml::from_str(file_content)?), &env::current_dir()?)
+}
+
+fn parse_config_value(mut config_value: toml::Value, config_dir: &Path) -> Result<Config> {
+    if let Some(config_table) = config_value.as_table_mut() {
+        resolve_profiles(config_table)?;
+    }
+    let mut config: Config = config_value.try_into()?;
+
+    for (profile_name, profile) in &mut config.profiles {
+        resolve_profile_paths(profile_name, profile, config_dir)?;
+    }
+
+    for (profile_name, profile) in &config.profiles {
+        if profile.backup && profile.backup_dir.is_none() {
+            anyhow::bail!(
+                "Backup is enabled in profile '{profile_name}', but backup_dir isn't set"
+            );
+        }
+        if let Some(pkg_ext) =
+            profile.pkg_extensions.iter().find(|x| !PKG_EXTENSIONS.contains(&x.as_str()))
+        {
+            anyhow::bail!("Unknown package extension '{pkg_ext}' in profile '{profile_name}'");
+        }
+        if let Some(signer) =
+            profile.allowed_signers.iter().find(|x| sig_utils::normalize_fingerprint(x).is_none())
+        {
+            anyhow::bail!(
+                "Invalid key fingerprint '{signer}' in allowed_signers of profile \
+                 '{profile_name}', full fingerprint is required"
+            );
+        }
+        let name_patterns = [&profile.sync_new, &profile.sync_removed]
+            .into_iter()
+            .flat_map(|x| x.allow.iter().chain(&x.deny))
+            .chain(profile.reference_repos.iter().flat_map(|x| &x.filter));
+        for name_pattern in name_patterns {
+            if let Err(pattern_err) = glob::Pattern::new(name_pattern) {
+                anyhow::bail!(
+                    "Invalid package name pattern '{name_pattern}' in profile '{profile_name}': \
+                     {pattern_err}"
+                );
+            }
+        }
+    }
+
     Ok(config)
 }
 
+// Expands the templates in the profile paths and resolves the relative paths:
+// 'repo' against the config dir, the rest of the paths against the repo dir
+fn resolve_profile_paths(
+    profile_name: &str,
+    profile: &mut Profile,
+    config_dir: &Path,
+) -> Result<()> {
+    let arch = env::consts::ARCH;
+    let env_lookup = |name: &str| env::var(name).ok();
+    let repo_placeholders = [("profile", profile_name), ("arch", arch)];
+    let repo = expand_path_template(&profile.repo, &repo_placeholders, env_lookup)
+        .with_context(|| format!("Invalid 'repo' in profile '{profile_name}'"))?;
+    profile.repo = resolve_relative_path(&repo, config_dir);
+
+    let repo_dir = Path::new(&profile.repo).parent().unwrap().to_path_buf();
+    let repo_name = pkg_utils::get_repo_db_prefix(&profile.repo);
+    let placeholders = [("profile", profile_name), ("arch", arch), ("repo", &repo_name)];
+    let profile_paths = [
+        ("backup_dir", profile.backup_dir.as_mut()),
+        ("debug_dir", profile.debug_dir.as_mut()),
+        ("reference_repo", profile.reference_repo.as_mut()),
+    ]
+    .into_iter()
+    .chain(profile.reference_repos.iter_mut().map(|x| ("reference_repos", Some(&mut x.path))));
+    for (key, path) in profile_paths {
+        let Some(path) = path else {
+            continue;
+        };
+        let expanded_path = expand_path_template(path, &placeholders, env_lookup)
+            .with_context(|| format!("Invalid '{key}' in profile '{profile_name}'"))?;
+        *path = if download::is_remote_url(&expanded_path) {
+            expanded_path
+        } else {
+            resolve_relative_path(&expanded_path, &repo_dir)
+        };
+    }
+
+    Ok(())
+}
+
+// Expands '~' at the start, '${ENV}' variables and '{name}' placeholders in the path,
+// e.g '~/repos/{arch}/{profile}' -> '/home/testuser/repos/x86_64/repof'.
+// The env variables are looked up with the given function
+fn expand_path_template(
+    template: &str,
+    placeholders: &[(&str, &str)],
+    env_lookup: impl Fn(&str) -> Option<String>,
+) -> Result<String> {
+    let mut expanded_path = String::new();
+    let mut rest = template;
+    if rest == "~" || rest.starts_with("~/") {
+        let Some(home) = env_lookup("HOME").filter(|x| !x.is_empty()) else {
+            anyhow::bail!("Failed to expand '~' in path '{template}', HOME is not set");
+        };
+        expanded_path.push_str(&home);
+        rest = &rest[1..];
+    }
+
+    while let Some(start_pos) = rest.find('{') {
+        let is_env_var = rest[..start_pos].ends_with('$');
+        expanded_path.push_str(&rest[..start_pos - usize::from(is_env_var)]);
+        let Some(name_len) = rest[start_pos..].find('}') else {
+            anyhow::bail!("Unclosed '{{' in path '{template}'");
+        };
+        let name = &rest[start_pos + 1..start_pos + name_len];
+        if is_env_var {
+            let Some(value) = env_lookup(name) else {
+                anyhow::bail!("Environment variable '{name}' in path '{template}' is not set");
+            };
+            expanded_path.push_str(&value);
+        } else {
+            let Some((_, value)) = placeholders.iter().find(|(x, _)| *x == name) else {
+                anyhow::bail!("Unknown placeholder '{{{name}}}' in path '{template}'");
+            };
+            expanded_path.push_str(value);
+        }
+        rest = &rest[start_pos + name_len + 1..];
+    }
+    expanded_path.push_str(rest);
+
+    Ok(expanded_path)
+}
+
+// Resolves the relative path against the base dir, and normalizes it (e.g removes '..')
+fn resolve_relative_path(path: &str, base_dir: &Path) -> String {
+    let mut resolved_path = PathBuf::new();
+    for component in base_dir.join(path).components() {
+        match component {
+            Component::CurDir => {},
+            Component::ParentDir => {
+                resolved_path.pop();
+            },
+            _ => resolved_path.push(component),
+        }
+    }
+    resolved_path.to_str().unwrap().to_owned()
+}
+
+// Resolves the settings of the profiles in layers, the later layer wins:
+// [defaults] < inherited profiles (starting from the top one) < profile itself.
+// The settings are replaced as a whole, e.g 'sync_new' table isn't merged.
+// The inherited 'repo' is taken only if it's the template with '{profile}', so the profiles
+// never share the repo DB by accident
+fn resolve_profiles(config_table: &mut toml::Table) -> Result<()> {
+    let defaults = match config_table.remove("defaults") {
+        Some(toml::Value::Table(defaults)) => defaults,
+        Some(_) => anyhow::bail!("Invalid 'defaults' in config, expected table"),
+        None => toml::Table::new(),
+    };
+    if defaults.contains_key("repo") || defaults.contains_key("inherits") {
+        anyhow::bail!("'repo' and 'inherits' can't be set in 'defaults'");
+    }
+
+    let Some(toml::Value::Table(profiles)) = config_table.get("profiles") else {
+        return Ok(());
+    };
+    let mut resolved_profiles = toml::Table::new();
+    for profile_name in profiles.keys() {
+        let mut resolved_profile = defaults.clone();
+        for layer_name in get_inheritance_chain(profiles, profile_name)?.into_iter().rev() {
+            let Some(layer) = profiles[layer_name].as_table() else {
+                continue;
+            };
+            let is_inherited = layer_name != profile_name;
+            resolved_profile.extend(
+                layer.clone().into_iter().filter(|(key, value)| {
+                    !is_inherited || key != "repo" || is_repo_template(value)
+                }),
+            );
+        }
+        if !resolved_profile.contains_key("repo")
+            && profiles[profile_name].get("inherits").is_some()
+        {
+            anyhow::bail!(
+                "Profile '{profile_name}' must set 'repo', the inherited one is taken only if it \
+                 contains '{{profile}}'"
+            );
+        }
+        resolved_profiles.insert(profile_name.clone(), toml::Value::Table(resolved_profile));
+    }
+    config_table.insert("profiles".to_owned(), toml::Value::Table(resolved_profiles));
+
+    Ok(())
+}
+
+// Checks if the repo path is different for each profile, e.g '/srv/{profile}/{profile}.db.tar.zst'
+fn is_repo_template(repo: &toml::Value) -> bool {
+    repo.as_str().is_some_and(|x| x.contains("{profile}"))
+}
+
+// Gets the profile followed by the profiles it inherits from
+fn get_inheritance_chain<'a>(
+    profiles: &'a toml::Table,
+    profile_name: &'a str,
+) -> Result<Vec<&'a str>> {
+    let mut inheritance_chain = vec![profile_name];
+    let mut current_name = profile_name;
+    while let Some(parent_name) = profiles[current_name].get("inherits") {
+        let Some(parent_name) = parent_name.as_str() else {
+            anyhow::bail!("Invalid 'inherits' in profile '{current_name}', expected profile name");
+        };
+        if !profiles.contains_key(parent_name) {
+            anyhow::bail!("Profile '{current_name}' inherits unknown profile '{parent_name}'");
+        }
+        let is_cycle = inheritance_chain.contains(&parent_name);
+        inheritance_chain.push(parent_name);
+        if is_cycle {
+            anyhow::bail!("Found inheritance cycle: {}", inheritance_chain.join(" -> "));
+        }
+        current_name = parent_name;
+    }
+    Ok(inheritance_chain)
+}
+
+// Checks if the package name matches any of the glob patterns (e.g 'linux-*')
+fn matches_any_pattern(patterns: &[String], pkgname: &str) -> bool {
+    patterns.iter().any(|x| glob::Pattern::new(x).is_ok_and(|pattern| pattern.matches(pkgname)))
+}
+
 fn default_add_params() -> Vec<String> {
     vec!["--sign".to_string(), "--include-sigs".to_string(), "--verify".to_string()]
 }
@@ -70,6 +603,10 @@ fn default_rm_params() -> Vec<String> {
     vec!["--sign".to_string()]
 }
 
+fn default_pkg_extensions() -> Vec<String> {
+    PKG_EXTENSIONS.map(String::from).to_vec()
+}
+
 fn default_require_signature() -> bool {
     true
 }
@@ -82,9 +619,14 @@ fn default_interactive() -> bool {
     false
 }
 
+fn default_lock_timeout() -> u64 {
+    60
+}
+
 #[cfg(test)]
 mod tests {
     use crate::config::*;
+    use crate::utils;
 
     #[test]
     fn parse_example_config() {
@@ -96,41 +638,362 @@ mod tests {
             profiles: HashMap::from([
                 ("repof".to_string(), Profile {
                     repo: "/home/testuser/repos/x86_64/os/repof/repof.db.tar.zst".to_string(),
+                    inherits: None,
                     add_params: vec!["--sign".to_string(), "--include-sigs".to_string()],
                     rm_params: vec!["--sign".to_string()],
                     require_signature: true,
+                    keyring: None,
+                    allowed_signers: vec![],
                     backup: true,
                     backup_num: None,
                     backup_dir: Some("/home/testuser/backup_repos/repof".to_string()),
                     debug_dir: Some("/home/testuser/debug_repos/repof".to_string()),
+                    debug_keep_num: None,
+                    debug_keep_matching: false,
                     interactive: false,
                     reference_repo: Some(
                         "/home/testanotheruser/repos/reposecond-super/reposecond-super.db.tar.zst"
                             .to_string(),
                     ),
+                    reference_repos: vec![],
+                    db_backend: DbBackend::RepoAdd,
+                    pkg_extensions: default_pkg_extensions(),
+                    sync_new: SyncMode::default(),
+                    sync_removed: SyncMode::default(),
+                    lock_timeout: default_lock_timeout(),
                 }),
                 ("reposecond".to_string(), Profile {
                     repo: "/home/testuser/repos/x86_64/os/reposecond/reposecond.db.tar.zst"
                         .to_string(),
+                    inherits: Some("repof".to_string()),
                     add_params: vec!["--sign".to_string(), "--include-sigs".to_string()],
                     rm_params: vec!["--sign".to_string()],
                     require_signature: true,
+                    keyring: None,
+                    allowed_signers: vec![],
                     backup: true,
                     backup_num: None,
                     backup_dir: Some("/home/testuser/backup_repos/reposecond".to_string()),
                     debug_dir: Some("/home/testuser/debug_repos/reposecond".to_string()),
+                    debug_keep_num: None,
+                    debug_keep_matching: false,
                     interactive: false,
                     reference_repo: Some(
                         "/home/testanotheruser/repos/reposecond-super/reposecond-super.db.tar.zst"
                             .to_string(),
                     ),
+                    reference_repos: vec![],
+                    db_backend: DbBackend::RepoAdd,
+                    pkg_extensions: default_pkg_extensions(),
+                    sync_new: SyncMode::default(),
+                    sync_removed: SyncMode::default(),
+                    lock_timeout: default_lock_timeout(),
                 }),
             ]),
+            log: LogConfig::default(),
         };
 
         assert_eq!(parsed_config.unwrap(), expected_config);
     }
 
+    #[test]
+    fn test_config_dropins() {
+        let temp_dir = utils::create_temporary_directory(None).unwrap();
+        let config_path = format!("{temp_dir}/config.toml");
+        fs::write(
+            &config_path,
+            r#"
+[log]
+format = "json"
+
+[profiles.repof]
+repo = "/home/testuser/repos/x86_64/os/repof/repof.db.tar.zst"
+backup = true
+backup_dir = "/home/testuser/backup_repos/repof"
+
+[profiles.reposecond]
+repo = "/home/testuser/repos/x86_64/os/reposecond/reposecond.db.tar.zst"
+"#,
+        )
+        .unwrap();
+
+        // no drop-in dir
+        let config = parse_config_file(&config_path).unwrap();
+        assert_eq!(config.profiles.len(), 2);
+        assert!(config.profiles["repof"].backup);
+
+        fs::create_dir(format!("{temp_dir}/config.d")).unwrap();
+        fs::write(
+            format!("{temp_dir}/config.d/20-repof.toml"),
+            r#"
+[profiles.repof]
+repo = "/srv/repos/repof/repof.db.tar.zst"
+"#,
+        )
+        .unwrap();
+        fs::write(
+            format!("{temp_dir}/config.d/10-repof.toml"),
+            r#"
+[log]
+journald = true
+
+[profiles.repof]
+repo = "/srv/old-repos/repof/repof.db.tar.zst"
+
+[profiles.repothird]
+repo = "/srv/repos/repothird/repothird.db.tar.zst"
+"#,
+        )
+        .unwrap();
+        fs::write(format!("{temp_dir}/config.d/30-ignored.toml.bak"), "invalid").unwrap();
+
+        // the later file wins, the whole profile is replaced
+        let config = parse_config_file(&config_path).unwrap();
+        assert_eq!(config.profiles.len(), 3);
+        assert_eq!(config.profiles["repof"].repo, "/srv/repos/repof/repof.db.tar.zst");
+        assert!(!config.profiles["repof"].backup);
+        assert_eq!(config.profiles["repothird"].repo, "/srv/repos/repothird/repothird.db.tar.zst");
+        assert_eq!(config.log, LogConfig {
+            format: LogFormat::Json,
+            journald: true,
+            ..Default::default()
+        });
+
+        // the drop-ins are validated too
+        fs::write(
+            format!("{temp_dir}/config.d/40-invalid.toml"),
+            r#"
+[profiles.repof]
+repo = "/srv/repos/repof/repof.db.tar.zst"
+pkg_extensions = [".zip"]
+"#,
+        )
+        .unwrap();
+        assert!(parse_config_file(&config_path).is_err());
+
+        fs::remove_dir_all(temp_dir).unwrap();
+    }
+
+    #[test]
+    fn test_get_config_search_paths() {
+        assert_eq!(
+            get_config_search_paths(Some("/xdg/config".to_owned()), Some("/home/user".to_owned())),
+            vec!["/xdg/config/repo-manage/config.toml", SYSTEM_CONFIG_PATH]
+        );
+        assert_eq!(
+            get_config_search_paths(Some("relative".to_owned()), Some("/home/user".to_owned())),
+            vec!["/home/user/.config/repo-manage/config.toml", SYSTEM_CONFIG_PATH]
+        );
+        assert_eq!(get_config_search_paths(None, None), vec![SYSTEM_CONFIG_PATH]);
+
+        assert!(get_config_path(Some("/nonexistent/repo-manage/config.toml")).is_err());
+        assert_eq!(get_config_path(Some("example-config.toml")).unwrap(), "example-config.toml");
+    }
+
+    #[test]
+    fn test_profile_inheritance() {
+        let config_str = r#"
+[defaults]
+backup = true
+backup_dir = "/home/testuser/backup_repos/{repo}"
+backup_num = 3
+rm_params = []
+
+[profiles.base]
+repo = "/home/testuser/repos/x86_64/os/base/base.db.tar.zst"
+backup_num = 5
+interactive = true
+
+[profiles.base.sync_new]
+enabled = true
+deny = ["*-git"]
+
+[profiles.repof]
+inherits = "base"
+repo = "/home/testuser/repos/x86_64/os/repof/repof.db.tar.zst"
+backup = false
+
+[profiles.repof.sync_new]
+enabled = true
+"#;
+
+        let config = parse_config_content(config_str).unwrap();
+        let base_profile = &config.profiles["base"];
+        assert!(base_profile.backup);
+        assert_eq!(base_profile.backup_num, Some(5));
+        assert!(base_profile.rm_params.is_empty());
+        assert_eq!(base_profile.add_params, default_add_params());
+
+        // profile < inherited profile < defaults, the tables are replaced as a whole
+        let repof_profile = &config.profiles["repof"];
+        assert_eq!(repof_profile.inherits.as_deref(), Some("base"));
+        assert_eq!(repof_profile.repo, "/home/testuser/repos/x86_64/os/repof/repof.db.tar.zst");
+        assert!(!repof_profile.backup);
+        assert_eq!(repof_profile.backup_num, Some(5));
+        assert!(repof_profile.interactive);
+        assert!(repof_profile.rm_params.is_empty());
+        assert_eq!(repof_profile.sync_new, SyncMode { enabled: true, ..Default::default() });
+        assert_eq!(repof_profile.backup_dir.as_deref(), Some("/home/testuser/backup_repos/repof"));
+
+        let config_str = r#"
+[profiles.repof]
+inherits = "unknown"
+repo = "/home/testuser/repos/x86_64/os/repof/repof.db.tar.zst"
+"#;
+        let parse_err = parse_config_content(config_str).unwrap_err();
+        assert!(parse_err.to_string().contains("inherits unknown profile 'unknown'"));
+
+        let config_str = r#"
+[profiles.repof]
+inherits = "reposecond"
+repo = "/home/testuser/repos/x86_64/os/repof/repof.db.tar.zst"
+
+[profiles.reposecond]
+inherits = "repof"
+repo = "/home/testuser/repos/x86_64/os/reposecond/reposecond.db.tar.zst"
+"#;
+        let parse_err = parse_config_content(config_str).unwrap_err();
+        assert!(parse_err.to_string().contains("cycle: repof -> reposecond -> repof"));
+
+        let config_str = r#"
+[defaults]
+repo = "/home/testuser/repos/x86_64/os/repof/repof.db.tar.zst"
+"#;
+        assert!(parse_config_content(config_str).is_err());
+
+        // the literal repo isn't inherited, so the child doesn't write into the parent's DB
+        let config_str = r#"
+[profiles.repof]
+repo = "/home/testuser/repos/x86_64/os/repof/repof.db.tar.zst"
+
+[profiles.reposecond]
+inherits = "repof"
+"#;
+        let parse_err = parse_config_content(config_str).unwrap_err();
+        assert!(parse_err.to_string().contains("Profile 'reposecond' must set 'repo'"));
+
+        // the repo template is resolved for each profile
+        let config_str = r#"
+[profiles.base]
+repo = "/home/testuser/repos/x86_64/os/{profile}/{profile}.db.tar.zst"
+
+[profiles.repof]
+inherits = "base"
+"#;
+        let config = parse_config_content(config_str).unwrap();
+        assert_eq!(
+            config.profiles["base"].repo,
+            "/home/testuser/repos/x86_64/os/base/base.db.tar.zst"
+        );
+        assert_eq!(
+            config.profiles["repof"].repo,
+            "/home/testuser/repos/x86_64/os/repof/repof.db.tar.zst"
+        );
+    }
+
+    #[test]
+    fn test_profile_paths() {
+        let temp_dir = utils::create_temporary_directory(None).unwrap();
+        let config_path = format!("{temp_dir}/config.toml");
+        let arch = env::consts::ARCH;
+        fs::write(
+            &config_path,
+            r#"
+[defaults]
+backup = true
+backup_dir = "../backup/{repo}"
+debug_dir = "./debug"
+
+[profiles.repof]
+repo = "repos/{arch}/{profile}/{profile}.db.tar.zst"
+reference_repo = "https://mirror.example.org/{repo}/{arch}/{repo}.db.tar.zst"
+
+[[profiles.repof.reference_repos]]
+path = "/mnt/mirror/{arch}/staging.db.tar.zst"
+
+[profiles.reposecond]
+repo = "/srv/repos/reposecond.db.tar.zst"
+backup_dir = "/srv/repos/"
+"#,
+        )
+        .unwrap();
+
+        let config = parse_config_file(&config_path).unwrap();
+        let repof_profile = &config.profiles["repof"];
+        assert_eq!(repof_profile.repo, format!("{temp_dir}/repos/{arch}/repof/repof.db.tar.zst"));
+        assert_eq!(
+            repof_profile.backup_dir.as_deref(),
+            Some(format!("{temp_dir}/repos/{arch}/backup/repof").as_str())
+        );
+        assert_eq!(
+            repof_profile.debug_dir.as_deref(),
+            Some(format!("{temp_dir}/repos/{arch}/repof/debug").as_str())
+        );
+        assert_eq!(
+            repof_profile.reference_repo.as_deref(),
+            Some(format!("https://mirror.example.org/repof/{arch}/repof.db.tar.zst").as_str())
+        );
+        assert_eq!(
+            repof_profile.reference_repos[0].path,
+            format!("/mnt/mirror/{arch}/staging.db.tar.zst")
+        );
+        assert!(repof_profile.get_backup_dir().is_some());
+
+        // the backup dir is the repo dir
+        let reposecond_profile = &config.profiles["reposecond"];
+        assert_eq!(reposecond_profile.backup_dir.as_deref(), Some("/srv/repos"));
+        assert_eq!(reposecond_profile.get_backup_dir(), None);
+
+        fs::remove_dir_all(temp_dir).unwrap();
+    }
+
+    #[test]
+    fn test_expand_path_template() {
+        let placeholders = [("profile", "repof"), ("arch", "x86_64"), ("repo", "repof")];
+        let env_vars = HashMap::from([("HOME", "/home/testuser"), ("MIRROR_DIR", "/mnt/mirror")]);
+        let expand = |template| {
+            expand_path_template(template, &placeholders, |name| {
+                env_vars.get(name).map(|x| x.to_string())
+            })
+        };
+        assert_eq!(
+            expand("/srv/{arch}/{profile}/{repo}.db.tar.zst").unwrap(),
+            "/srv/x86_64/repof/repof.db.tar.zst"
+        );
+        assert_eq!(expand("~/repos/{repo}").unwrap(), "/home/testuser/repos/repof");
+        assert_eq!(expand("${MIRROR_DIR}/{arch}").unwrap(), "/mnt/mirror/x86_64");
+        assert_eq!(expand("/srv/$repos/{repo}").unwrap(), "/srv/$repos/repof");
+        assert!(expand("/srv/{unknown}").is_err());
+        assert!(expand("/srv/{repo").is_err());
+        assert!(expand("/srv/${UNSET_VAR}").is_err());
+
+        assert_eq!(resolve_relative_path("../backup", Path::new("/srv/repos")), "/srv/backup");
+        assert_eq!(resolve_relative_path("/srv/debug/", Path::new("/srv/repos")), "/srv/debug");
+    }
+
+    #[test]
+    fn test_render_profile() {
+        let profile = Profile {
+            repo: "/home/testuser/repos/x86_64/os/repof/repof.db.tar.zst".to_owned(),
+            inherits: Some("base".to_owned()),
+            add_params: default_add_params(),
+            reference_repos: vec![ReferenceRepo {
+                path: "/home/testuser/repos/staging/staging.db.tar.zst".to_owned(),
+                priority: 10,
+                filter: vec![],
+            }],
+            ..Default::default()
+        };
+
+        // the rendered profile is a valid profile config
+        let rendered_profile = profile.render(OutputFormat::Text).unwrap();
+        assert!(rendered_profile.contains("inherits = \"base\"\n"), "{rendered_profile}");
+        assert_eq!(toml::from_str::<Profile>(&rendered_profile).unwrap(), profile);
+
+        let rendered_profile = profile.render(OutputFormat::Json).unwrap();
+        assert_eq!(serde_json::from_str::<Profile>(&rendered_profile).unwrap(), profile);
+    }
+
     #[test]
     fn test_missing_required_field() {
         let config_str = r#"
@@ -143,6 +1006,223 @@ debug_package_dir = "/home/testuser/debug_repos/repof"
         assert!(result.is_err());
     }
 
+    #[test]
+    fn test_log_config() {
+        let config_str = r#"
+[log]
+format = "json"
+file = "/var/log/repo-manage/repo-manage.log"
+max_files = 2
+
+[profiles.repof]
+repo = "/home/testuser/repos/x86_64/os/repof/repof.db.tar.zst"
+"#;
+
+        let result = parse_config_content(config_str).unwrap();
+        assert_eq!(result.log, LogConfig {
+            format: LogFormat::Json,
+            file: Some("/var/log/repo-manage/repo-manage.log".to_owned()),
+            max_files: 2,
+            ..Default::default()
+        });
+
+        let config_str = r#"
+[log]
+format = "xml"
+"#;
+        assert!(parse_config_content(config_str).is_err());
+    }
+
+    #[test]
+    fn test_db_backend() {
+        let config_str = r#"
+[profiles.repof]
+repo = "/home/testuser/repos/x86_64/os/repof/repof.db.tar.zst"
+db_backend = "native"
+"#;
+
+        let result = parse_config_content(config_str).unwrap();
+        assert_eq!(result.profiles["repof"].db_backend, DbBackend::Native);
+
+        let config_str = r#"
+[profiles.repof]
+repo = "/home/testuser/repos/x86_64/os/repof/repof.db.tar.zst"
+db_backend = "something"
+"#;
+        assert!(parse_config_content(config_str).is_err());
+    }
+
+    #[test]
+    fn test_pkg_extensions() {
+        let config_str = r#"
+[profiles.repof]
+repo = "/home/testuser/repos/x86_64/os/repof/repof.db.tar.zst"
+pkg_extensions = [".pkg.tar.zst", ".pkg.tar.xz"]
+"#;
+
+        let result = parse_config_content(config_str).unwrap();
+        assert_eq!(result.profiles["repof"].pkg_extensions, [".pkg.tar.zst", ".pkg.tar.xz"]);
+
+        let config_str = r#"
+[profiles.repof]
+repo = "/home/testuser/repos/x86_64/os/repof/repof.db.tar.zst"
+pkg_extensions = [".pkg.tar.rar"]
+"#;
+        assert!(parse_config_content(config_str).is_err());
+    }
+
+    #[test]
+    fn test_keyring() {
+        let config_str = r#"
+[profiles.repof]
+repo = "/home/testuser/repos/x86_64/os/repof/repof.db.tar.zst"
+keyring = "/etc/repo-manage/trustedkeys.gpg"
+"#;
+
+        let result = parse_config_content(config_str).unwrap();
+        assert_eq!(
+            result.profiles["repof"].keyring.as_deref(),
+            Some("/etc/repo-manage/trustedkeys.gpg")
+        );
+    }
+
+    #[test]
+    fn test_allowed_signers() {
+        let config_str = r#"
+[profiles.repof]
+repo = "/home/testuser/repos/x86_64/os/repof/repof.db.tar.zst"
+allowed_signers = ["3B94A80E50A477C7EC4C1E0D0F3E2D5F7C1A9B3D"]
+"#;
+
+        let result = parse_config_content(config_str).unwrap();
+        assert_eq!(result.profiles["repof"].allowed_signers, [
+            "3B94A80E50A477C7EC4C1E0D0F3E2D5F7C1A9B3D"
+        ]);
+
+        let config_str = r#"
+[profiles.repof]
+repo = "/home/testuser/repos/x86_64/os/repof/repof.db.tar.zst"
+allowed_signers = ["7C1A9B3D"]
+"#;
+        assert!(parse_config_content(config_str).is_err());
+    }
+
+    #[test]
+    fn test_get_debug_profile() {
+        let profile = Profile {
+            repo: "/home/testuser/repos/x86_64/os/repof/repof.db.tar.zst".to_string(),
+            debug_dir: Some("/home/testuser/debug_repos/repof/".to_string()),
+            backup: true,
+            backup_dir: Some("/home/testuser/backup_repos/repof".to_string()),
+            ..Default::default()
+        };
+        let debug_profile = profile.get_debug_profile().unwrap();
+        assert_eq!(debug_profile.repo, "/home/testuser/debug_repos/repof/repof-debug.db.tar.zst");
+        assert_eq!(debug_profile.debug_dir, None);
+        assert_eq!(debug_profile.backup_dir, profile.backup_dir);
+
+        // debug packages are kept in the repo itself
+        let profile = Profile {
+            debug_dir: Some("/home/testuser/repos/x86_64/os/repof".to_string()),
+            ..profile
+        };
+        assert_eq!(profile.get_debug_profile(), None);
+        let profile = Profile { debug_dir: None, ..profile };
+        assert_eq!(profile.get_debug_profile(), None);
+    }
+
+    #[test]
+    fn test_sync_modes() {
+        let config_str = r#"
+[profiles.repof]
+repo = "/home/testuser/repos/x86_64/os/repof/repof.db.tar.zst"
+
+[profiles.repof.sync_new]
+enabled = true
+deny = ["*-git"]
+
+[profiles.repof.sync_removed]
+enabled = true
+allow = ["linux-*", "dash"]
+"#;
+
+        let result = parse_config_content(config_str).unwrap();
+        let profile = &result.profiles["repof"];
+        assert!(profile.sync_new.is_allowed("dash"));
+        assert!(!profile.sync_new.is_allowed("dwl-git"));
+        assert!(profile.sync_removed.is_allowed("linux-cachyos"));
+        assert!(profile.sync_removed.is_allowed("dash"));
+        assert!(!profile.sync_removed.is_allowed("st"));
+
+        // disabled mode doesn't sync anything
+        assert!(
+            !SyncMode { allow: vec!["dash".to_string()], ..Default::default() }.is_allowed("dash")
+        );
+
+        let config_str = r#"
+[profiles.repof]
+repo = "/home/testuser/repos/x86_64/os/repof/repof.db.tar.zst"
+sync_new = { enabled = true, allow = ["linux-***"] }
+"#;
+        assert!(parse_config_content(config_str).is_err());
+    }
+
+    #[test]
+    fn test_reference_repos() {
+        let config_str = r#"
+[profiles.repof]
+repo = "/home/testuser/repos/x86_64/os/repof/repof.db.tar.zst"
+reference_repo = "/home/testuser/repos/reposecond/reposecond.db.tar.zst"
+
+[[profiles.repof.reference_repos]]
+path = "https://mirror.example.org/repo/x86_64/staging/staging.db.tar.zst"
+priority = 10
+filter = ["linux-*"]
+
+[[profiles.repof.reference_repos]]
+path = "/home/testuser/repos/fallback/fallback.db.tar.zst"
+priority = -5
+"#;
+
+        let result = parse_config_content(config_str).unwrap();
+        let reference_repos = result.profiles["repof"].get_reference_repos();
+        assert_eq!(reference_repos, [
+            ReferenceRepo {
+                path: "https://mirror.example.org/repo/x86_64/staging/staging.db.tar.zst"
+                    .to_string(),
+                priority: 10,
+                filter: vec!["linux-*".to_string()],
+            },
+            ReferenceRepo {
+                path: "/home/testuser/repos/reposecond/reposecond.db.tar.zst".to_string(),
+                priority: 0,
+                filter: vec![],
+            },
+            ReferenceRepo {
+                path: "/home/testuser/repos/fallback/fallback.db.tar.zst".to_string(),
+                priority: -5,
+                filter: vec![],
+            },
+        ]);
+        assert!(reference_repos[0].is_allowed("linux-cachyos"));
+        assert!(!reference_repos[0].is_allowed("dash"));
+        assert!(reference_repos[1].is_allowed("dash"));
+
+        let config_str = r#"
+[profiles.repof]
+repo = "/home/testuser/repos/x86_64/os/repof/repof.db.tar.zst"
+reference_repos = [{ path = "/home/testuser/repos/fallback/fallback.db.tar.zst", filter = ["linux-***"] }]
+"#;
+        assert!(parse_config_content(config_str).is_err());
+
+        let config_str = r#"
+[profiles.repof]
+repo = "/home/testuser/repos/x86_64/os/repof/repof.db.tar.zst"
+"#;
+        let result = parse_config_content(config_str).unwrap();
+        assert!(result.profiles["repof"].get_reference_repos().is_empty());
+    }
+
     #[test]
     fn test_empty_config() {
         let config_str = "";
@@ -0,0 +1,260 @@
+use crate::journal::Journal;
+use crate::prompt;
+
+use std::cell::{Cell, RefCell};
+use std::fs;
+use std::io::{self, BufRead, IsTerminal, Write};
+
+use anyhow::Result;
+
+/// Options of the current run, which are shared by all commands
+#[derive(Debug, Default)]
+pub struct Context {
+    /// Only print the operations which would be made, without touching anything
+    pub dry_run: bool,
+    /// Ask for confirmation before destructive actions
+    interactive: bool,
+    /// User answered 'all' to one of the prompts, don't ask anymore
+    confirmed_all: Cell<bool>,
+    /// Journal of the running operation, all file and DB changes are recorded there
+    journal: RefCell<Option<Journal>>,
+}
+
+impl Context {
+    pub fn new(dry_run: bool) -> Self {
+        Self { dry_run, ..Default::default() }
+    }
+
+    // Enables the confirmation prompts, unless stdin is not a terminal
+    pub fn set_interactive(&mut self, interactive: bool) {
+        if interactive && !io::stdin().is_terminal() {
+            log::warn!("stdin is not a terminal, interactive mode is disabled");
+            self.interactive = false;
+            return;
+        }
+        self.interactive = interactive;
+    }
+
+    // Asks the user to confirm the action in interactive mode.
+    // Returns false if the action should be skipped, and fails if the user wants to quit
+    pub fn confirm(&self, question: &str) -> Result<bool> {
+        self.confirm_with(question, &mut io::stdin().lock(), &mut io::stderr())
+    }
+
+    fn confirm_with(
+        &self,
+        question: &str,
+        input: &mut impl BufRead,
+        output: &mut impl Write,
+    ) -> Result<bool> {
+        // nothing is changed in dry-run mode, so there is nothing to confirm
+        if !self.interactive || self.dry_run || self.confirmed_all.get() {
+            return Ok(true);
+        }
+
+        match prompt::ask(question, input, output)? {
+            prompt::Answer::Yes => Ok(true),
+            prompt::Answer::No => Ok(false),
+            prompt::Answer::All => {
+                self.confirmed_all.set(true);
+                Ok(true)
+            },
+            prompt::Answer::Quit => anyhow::bail!("Aborted by user"),
+        }
+    }
+
+    // Runs the operation on the repo with the journal, rolling back all changes made by it on
+    // failure. Nothing is changed in dry-run mode, so the journal isn't needed
+    pub fn run_journaled(
+        &self,
+        repo_db_path: &str,
+        command: &str,
+        operation: impl FnOnce() -> Result<()>,
+    ) -> Result<()> {
+        if self.dry_run {
+            return operation();
+        }
+
+        let journal = Journal::begin(repo_db_path, command)?;
+        *self.journal.borrow_mut() = Some(journal);
+        let op_result = operation();
+        let journal = self.journal.borrow_mut().take().unwrap();
+
+        if let Err(op_err) = op_result {
+            log::error!("Operation '{command}' failed, rolling back its changes");
+            if let Err(rollback_err) = journal.rollback() {
+                log::error!("Failed to roll back, it's retried on the next run: {rollback_err}");
+            }
+            return Err(op_err);
+        }
+        journal.finish()
+    }
+
+    // Links the other repo changed by the operation to its journal, before it's changed
+    pub fn link_repo(&self, repo_db_path: &str) -> Result<()> {
+        match self.journal.borrow_mut().as_mut() {
+            Some(journal) => journal.link_repo(repo_db_path),
+            None => Ok(()),
+        }
+    }
+
+    // Saves the repo DB into the journal before it's changed
+    pub fn save_repo_db(&self, repo_db_path: &str) -> Result<()> {
+        match self.journal.borrow_mut().as_mut() {
+            Some(journal) => journal.save_repo_db(repo_db_path),
+            None => Ok(()),
+        }
+    }
+
+    // Removes the file, or only prints it in dry-run mode
+    pub fn remove_file(&self, filepath: &str) -> Result<()> {
+        if self.dry_run {
+            println!("[dry-run] rm '{filepath}'");
+            return Ok(());
+        }
+        if let Some(journal) = self.journal.borrow_mut().as_mut() {
+            return journal.remove_file(filepath);
+        }
+        fs::remove_file(filepath)?;
+        Ok(())
+    }
+
+    // Moves the file, or only prints it in dry-run mode
+    pub fn rename_file(&self, src_path: &str, dest_path: &str) -> Result<()> {
+        if self.dry_run {
+            println!("[dry-run] mv '{src_path}' -> '{dest_path}'");
+            return Ok(());
+        }
+        if let Some(journal) = self.journal.borrow_mut().as_mut() {
+            return journal.rename_file(src_path, dest_path);
+        }
+        fs::rename(src_path, dest_path)?;
+        Ok(())
+    }
+
+    // Creates the directory with all of its parents, or only prints it in dry-run mode
+    pub fn create_dir_all(&self, dirpath: &str) -> Result<()> {
+        if self.dry_run {
+            println!("[dry-run] mkdir -p '{dirpath}'");
+            return Ok(());
+        }
+        if let Some(journal) = self.journal.borrow_mut().as_mut() {
+            return journal.create_dir_all(dirpath);
+        }
+        fs::create_dir_all(dirpath)?;
+        Ok(())
+    }
+
+    // Copies the file, or only prints it in dry-run mode
+    pub fn copy_file(&self, src_path: &str, dest_path: &str) -> Result<()> {
+        if self.dry_run {
+            println!("[dry-run] cp '{src_path}' -> '{dest_path}'");
+            return Ok(());
+        }
+        if let Some(journal) = self.journal.borrow_mut().as_mut() {
+            return journal.copy_file(src_path, dest_path);
+        }
+        fs::copy(src_path, dest_path)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::context::*;
+    use crate::{journal, utils};
+
+    use std::io::Cursor;
+    use std::path::Path;
+
+    #[test]
+    fn test_dry_run_doesnt_touch_files() {
+        let temp_dir = utils::create_temporary_directory(None).expect("Failed to create temp dir");
+        let filepath = format!("{temp_dir}/dash-0.5.12-1.1-x86_64.pkg.tar.zst");
+        let dest_path = format!("{temp_dir}/st-0.8.4-2-x86_64.pkg.tar.zst");
+        fs::write(&filepath, "package").unwrap();
+
+        let ctx = Context { dry_run: true, ..Default::default() };
+        ctx.create_dir_all(&format!("{temp_dir}/debug")).unwrap();
+        assert!(!Path::new(&format!("{temp_dir}/debug")).exists());
+        ctx.copy_file(&filepath, &dest_path).unwrap();
+        ctx.rename_file(&filepath, &dest_path).unwrap();
+        ctx.remove_file(&filepath).unwrap();
+        assert!(Path::new(&filepath).exists());
+        assert!(!Path::new(&dest_path).exists());
+
+        fs::remove_dir_all(temp_dir).unwrap();
+    }
+
+    #[test]
+    fn test_file_operations() {
+        let temp_dir = utils::create_temporary_directory(None).expect("Failed to create temp dir");
+        let filepath = format!("{temp_dir}/dash-0.5.12-1.1-x86_64.pkg.tar.zst");
+        let copy_path = format!("{temp_dir}/copy.pkg.tar.zst");
+        let dest_path = format!("{temp_dir}/moved.pkg.tar.zst");
+        fs::write(&filepath, "package").unwrap();
+
+        let ctx = Context::default();
+        ctx.copy_file(&filepath, &copy_path).unwrap();
+        ctx.rename_file(&filepath, &dest_path).unwrap();
+        assert!(!Path::new(&filepath).exists());
+        assert_eq!(fs::read_to_string(&copy_path).unwrap(), "package");
+        assert_eq!(fs::read_to_string(&dest_path).unwrap(), "package");
+
+        ctx.remove_file(&dest_path).unwrap();
+        assert!(!Path::new(&dest_path).exists());
+        assert!(ctx.remove_file(&dest_path).is_err());
+
+        fs::remove_dir_all(temp_dir).unwrap();
+    }
+
+    #[test]
+    fn test_run_journaled() {
+        let temp_dir = utils::create_temporary_directory(None).expect("Failed to create temp dir");
+        let repo_db_path = format!("{temp_dir}/repof.db.tar.zst");
+        let filepath = format!("{temp_dir}/dash-0.5.12-1.1-x86_64.pkg.tar.zst");
+        let dest_path = format!("{temp_dir}/moved.pkg.tar.zst");
+        fs::write(&filepath, "package").unwrap();
+
+        // failed operation is rolled back
+        let ctx = Context::default();
+        let op_result = ctx.run_journaled(&repo_db_path, "update", || {
+            ctx.rename_file(&filepath, &dest_path)?;
+            anyhow::bail!("repo-add failed!")
+        });
+        assert!(op_result.is_err());
+        assert!(Path::new(&filepath).exists());
+        assert!(!Path::new(&dest_path).exists());
+
+        ctx.run_journaled(&repo_db_path, "update", || ctx.rename_file(&filepath, &dest_path))
+            .unwrap();
+        assert!(!Path::new(&filepath).exists());
+        assert!(Path::new(&dest_path).exists());
+        // the journal is kept as the record of the last operation when it's done
+        assert!(!Path::new(&journal::get_journal_path(&repo_db_path)).exists());
+        assert!(Path::new(&journal::get_last_journal_path(&repo_db_path)).exists());
+
+        fs::remove_dir_all(temp_dir).unwrap();
+    }
+
+    #[test]
+    fn test_confirm() {
+        let ctx = Context::default();
+        // without interactive mode everything is confirmed
+        assert!(ctx.confirm_with("Remove?", &mut Cursor::new("n\n"), &mut vec![]).unwrap());
+
+        let ctx = Context { interactive: true, ..Default::default() };
+        let mut input = Cursor::new("n\ny\na\n");
+        assert!(!ctx.confirm_with("Remove?", &mut input, &mut vec![]).unwrap());
+        assert!(ctx.confirm_with("Remove?", &mut input, &mut vec![]).unwrap());
+        assert!(ctx.confirm_with("Remove?", &mut input, &mut vec![]).unwrap());
+        // after 'all' nothing is asked anymore
+        assert!(ctx.confirm_with("Remove?", &mut input, &mut vec![]).unwrap());
+
+        let ctx = Context { interactive: true, ..Default::default() };
+        assert!(ctx.confirm_with("Remove?", &mut Cursor::new("q\n"), &mut vec![]).is_err());
+
+        let ctx = Context { interactive: true, dry_run: true, ..Default::default() };
+        assert!(ctx.confirm_with("Remove?", &mut Cursor::new("n\n"), &mut vec![]).unwrap());
+    }
+}
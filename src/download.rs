@@ -0,0 +1,186 @@
+use crate::utils;
+
+use std::{fs, io};
+
+use anyhow::{Context, Result};
+
+/// Temporary directory for the downloaded files, which is removed on drop
+pub struct DownloadDir {
+    pub path: String,
+}
+
+impl DownloadDir {
+    pub fn new() -> Result<Self> {
+        let path = utils::create_temporary_directory(None)
+            .context("Failed to create temp dir for downloads")?;
+        Ok(Self { path })
+    }
+}
+
+impl Drop for DownloadDir {
+    fn drop(&mut self) {
+        if let Err(rm_err) = fs::remove_dir_all(&self.path) {
+            log::error!("Failed to remove download dir '{}': {rm_err}", self.path);
+        }
+    }
+}
+
+// Checks if the repo path is the URL of the remote (HTTP/HTTPS) repo
+pub fn is_remote_url(repo_path: &str) -> bool {
+    repo_path.starts_with("http://") || repo_path.starts_with("https://")
+}
+
+// Downloads the file from the URL into the destination path.
+// Returns false if the file doesn't exist on the server
+pub fn download_file(url: &str, dest_path: &str) -> Result<bool> {
+    log::debug!("Downloading '{url}' -> '{dest_path}'");
+
+    let response = match ureq::get(url).call() {
+        Ok(response) => response,
+        Err(ureq::Error::Status(404, _)) => return Ok(false),
+        Err(download_err) => anyhow::bail!("Failed to download '{url}': {download_err}"),
+    };
+
+    let mut dest_file = fs::File::create(dest_path)
+        .with_context(|| format!("Failed to create file '{dest_path}'"))?;
+    io::copy(&mut response.into_reader(), &mut dest_file)
+        .with_context(|| format!("Failed to download '{url}'"))?;
+
+    Ok(true)
+}
+
+// Downloads remote package files with their signatures into the download dir, so they can be
+// verified and copied as the local ones. Local package files are returned as is
+pub fn fetch_packages(pkg_list: &[String], download_dir: &str) -> Result<Vec<String>> {
+    let mut fetched_pkgs: Vec<String> = vec![];
+    for pkg_path in pkg_list {
+        if !is_remote_url(pkg_path) {
+            fetched_pkgs.push(pkg_path.clone());
+            continue;
+        }
+
+        let pkg_filename = pkg_path.rsplit('/').next().unwrap();
+        let dest_path = format!("{download_dir}/{pkg_filename}");
+        log::info!("Downloading pkg '{pkg_path}'");
+        if !download_file(pkg_path, &dest_path)? {
+            anyhow::bail!("Package file doesn't exist in ref repo: {pkg_path}");
+        }
+
+        // the signature is optional here, missing one is reported on verification
+        let sig_dest_path = format!("{dest_path}.sig");
+        if !download_file(&format!("{pkg_path}.sig"), &sig_dest_path)? {
+            log::debug!("Package signature doesn't exist in ref repo: {pkg_path}.sig");
+        }
+
+        fetched_pkgs.push(dest_path);
+    }
+
+    Ok(fetched_pkgs)
+}
+
+#[cfg(test)]
+pub mod tests {
+    use crate::download::*;
+
+    use std::io::{BufRead, BufReader, Write};
+    use std::net::TcpListener;
+    use std::path::Path;
+    use std::thread;
+
+    // Starts the static file server for the directory in background, returns its base URL
+    pub fn start_file_server(served_dir: &str) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").expect("Failed to bind test server");
+        let server_url = format!("http://{}", listener.local_addr().unwrap());
+        let served_dir = served_dir.to_owned();
+
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                let Ok(mut stream) = stream else {
+                    continue;
+                };
+                let mut request_line = String::new();
+                if BufReader::new(&stream).read_line(&mut request_line).is_err() {
+                    continue;
+                }
+
+                let request_path = request_line.split_whitespace().nth(1).unwrap_or("/");
+                let response = match fs::read(format!("{served_dir}{request_path}")) {
+                    Ok(content) => {
+                        let header = format!(
+                            "HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+                            content.len()
+                        );
+                        [header.into_bytes(), content].concat()
+                    },
+                    Err(_) => b"HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\nConnection: \
+                                close\r\n\r\n"
+                        .to_vec(),
+                };
+                let _ = stream.write_all(&response);
+            }
+        });
+
+        server_url
+    }
+
+    #[test]
+    fn test_is_remote_url() {
+        assert!(is_remote_url("https://mirror.cachyos.org/repo/x86_64/cachyos/cachyos.db"));
+        assert!(is_remote_url("http://localhost:8080/repof/repof.db.tar.zst"));
+        assert!(!is_remote_url("/home/testuser/repos/x86_64/os/repof/repof.db.tar.zst"));
+        assert!(!is_remote_url("file:///home/testuser/repos/repof.db.tar.zst"));
+    }
+
+    #[test]
+    fn test_download_file() {
+        let served_dir = utils::create_temporary_directory(None).unwrap();
+        let download_dir = DownloadDir::new().unwrap();
+        fs::write(format!("{served_dir}/repof.db.tar.zst"), "repo db").unwrap();
+
+        let server_url = start_file_server(&served_dir);
+        let dest_path = format!("{}/repof.db.tar.zst", download_dir.path);
+        assert!(download_file(&format!("{server_url}/repof.db.tar.zst"), &dest_path).unwrap());
+        assert_eq!(fs::read_to_string(&dest_path).unwrap(), "repo db");
+
+        assert!(!download_file(&format!("{server_url}/missing.db"), &dest_path).unwrap());
+
+        fs::remove_dir_all(served_dir).unwrap();
+    }
+
+    #[test]
+    fn test_fetch_packages() {
+        let served_dir = utils::create_temporary_directory(None).unwrap();
+        let download_dir = DownloadDir::new().unwrap();
+        let download_path = download_dir.path.clone();
+
+        let pkgfile = utils::create_test_package(&served_dir, "dash", "0.5.12-1.1", "x86_64");
+        fs::write(format!("{pkgfile}.sig"), "signature").unwrap();
+        let st_pkgfile = utils::create_test_package(&served_dir, "st", "0.8.4-2", "x86_64");
+
+        let server_url = start_file_server(&served_dir);
+        let pkg_list = vec![
+            format!("{server_url}/dash-0.5.12-1.1-x86_64.pkg.tar.zst"),
+            format!("{server_url}/st-0.8.4-2-x86_64.pkg.tar.zst"),
+            // local packages are not downloaded
+            st_pkgfile.clone(),
+        ];
+        let fetched_pkgs = fetch_packages(&pkg_list, &download_dir.path).unwrap();
+        assert_eq!(fetched_pkgs, [
+            format!("{download_path}/dash-0.5.12-1.1-x86_64.pkg.tar.zst"),
+            format!("{download_path}/st-0.8.4-2-x86_64.pkg.tar.zst"),
+            st_pkgfile,
+        ]);
+        assert_eq!(fs::read(&fetched_pkgs[0]).unwrap(), fs::read(&pkgfile).unwrap());
+        assert_eq!(fs::read_to_string(format!("{}.sig", fetched_pkgs[0])).unwrap(), "signature");
+        assert!(!Path::new(&format!("{}.sig", fetched_pkgs[1])).exists());
+
+        let missing_pkg = vec![format!("{server_url}/dwm-6.2-4-x86_64.pkg.tar.zst")];
+        assert!(fetch_packages(&missing_pkg, &download_dir.path).is_err());
+
+        // download dir is removed with all downloaded files
+        drop(download_dir);
+        assert!(!Path::new(&download_path).exists());
+
+        fs::remove_dir_all(served_dir).unwrap();
+    }
+}
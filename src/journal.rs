@@ -0,0 +1,860 @@
+use crate::pkg_utils;
+
+use std::path::Path;
+use std::time::SystemTime;
+use std::{fmt, fs};
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+
+/// Step of the operation, which can be rolled back
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+#[serde(tag = "op", rename_all = "kebab-case")]
+pub enum Step {
+    /// File was moved, rolled back by moving it back.
+    /// The file replaced at the destination is kept in the journal dir under the saved name
+    Rename {
+        src: String,
+        dest: String,
+        #[serde(default)]
+        replaced_name: Option<String>,
+    },
+    /// File was copied, rolled back by removing the copy.
+    /// The file replaced at the destination is kept in the journal dir under the saved name
+    Copy {
+        src: String,
+        dest: String,
+        #[serde(default)]
+        replaced_name: Option<String>,
+    },
+    /// File was removed, it's kept in the journal dir under the saved name
+    Remove { path: String, saved_name: String },
+    /// Directory was created, rolled back by removing it if it's empty
+    CreateDir { path: String },
+    /// Repo DB was changed, the DB files before the change are kept in the journal dir
+    ChangeDb { repo: String, saved_name: String },
+}
+
+impl fmt::Display for Step {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Rename { src, dest, .. } => write!(f, "mv '{src}' -> '{dest}'"),
+            Self::Copy { src, dest, .. } => write!(f, "cp '{src}' -> '{dest}'"),
+            Self::Remove { path, .. } => write!(f, "rm '{path}'"),
+            Self::CreateDir { path } => write!(f, "mkdir -p '{path}'"),
+            Self::ChangeDb { repo, .. } => write!(f, "change DB '{repo}'"),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum StepState {
+    /// The step is about to be made, it might be made partially if the process was killed
+    Planned,
+    Done,
+}
+
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+pub struct JournalEntry {
+    #[serde(flatten)]
+    pub step: Step,
+    pub state: StepState,
+}
+
+/// Journal of the steps made by the operation on the repo, stored next to the repo DB.
+/// The journal is kept as the record of the last operation when it's finished, so if it exists
+/// on the next run, the operation was interrupted.
+/// The other repos changed by the operation get the link to the journal, so the interrupted
+/// operation is found from them, and to the record as their last operation
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct Journal {
+    /// Command which started the operation
+    pub command: String,
+    /// Process which made the operation
+    pub pid: u32,
+    /// Unix time when the operation was started
+    #[serde(default)]
+    pub timestamp: u64,
+    pub steps: Vec<JournalEntry>,
+    /// Other repos changed by the operation, which have the link to the journal
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub linked_repos: Vec<String>,
+    /// Repo keeping the journal of the operation, if this is the link to it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub record_repo: Option<String>,
+    #[serde(skip)]
+    repo: String,
+    #[serde(skip)]
+    path: String,
+}
+
+impl Journal {
+    // Starts the journal of the operation on the repo
+    pub fn begin(repo_db_path: &str, command: &str) -> Result<Self> {
+        let path = get_journal_path(repo_db_path);
+        if Path::new(&path).exists() {
+            anyhow::bail!("Journal of the interrupted operation exists '{path}'");
+        }
+        fs::create_dir_all(get_journal_dir(&path))
+            .with_context(|| format!("Failed to create journal dir for '{path}'"))?;
+
+        let timestamp = SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .map(|x| x.as_secs())
+            .unwrap_or_default();
+        let journal = Self {
+            command: command.to_owned(),
+            pid: std::process::id(),
+            timestamp,
+            steps: vec![],
+            linked_repos: vec![],
+            record_repo: None,
+            repo: repo_db_path.to_owned(),
+            path,
+        };
+        journal.save()?;
+        Ok(journal)
+    }
+
+    // Loads the journal of the interrupted operation on the repo, if there is one
+    pub fn load(repo_db_path: &str) -> Result<Option<Self>> {
+        load_journal(get_journal_path(repo_db_path), repo_db_path)
+    }
+
+    // Loads the record of the last finished operation on the repo, if there is one.
+    // The link is followed to the record kept with the other repo, unless the other repo was
+    // changed after the operation
+    pub fn load_last(repo_db_path: &str) -> Result<Option<Self>> {
+        let Some(last_journal) = load_journal(get_last_journal_path(repo_db_path), repo_db_path)?
+        else {
+            return Ok(None);
+        };
+        let Some(record_repo) = &last_journal.record_repo else {
+            return Ok(Some(last_journal));
+        };
+        Ok(load_journal(get_last_journal_path(record_repo), record_repo)?
+            .filter(|x| last_journal.is_link_to(x)))
+    }
+
+    // Gets the repos changed by the operation
+    pub fn get_repos(&self) -> Vec<String> {
+        let mut repos: Vec<String> = vec![];
+        for entry in &self.steps {
+            match &entry.step {
+                Step::ChangeDb { repo, .. } if !repos.contains(repo) => repos.push(repo.clone()),
+                _ => {},
+            }
+        }
+        for repo in &self.linked_repos {
+            if !repos.contains(repo) {
+                repos.push(repo.clone());
+            }
+        }
+        repos
+    }
+
+    // Links the other repo changed by the operation to the journal, before it's changed.
+    // If the operation is interrupted, it's found and rolled back from the other repo as well
+    pub fn link_repo(&mut self, repo_db_path: &str) -> Result<()> {
+        if repo_db_path == self.repo || self.linked_repos.iter().any(|x| x == repo_db_path) {
+            return Ok(());
+        }
+        let link_path = get_journal_path(repo_db_path);
+        if Path::new(&link_path).exists() {
+            anyhow::bail!("Journal of the interrupted operation exists '{link_path}'");
+        }
+
+        self.linked_repos.push(repo_db_path.to_owned());
+        self.save()?;
+        self.make_link(repo_db_path, link_path).save()
+    }
+
+    // Finishes the operation, keeping the journal with the saved files as the record of the
+    // last operation, so it can be undone. The operation without any changes isn't recorded
+    pub fn finish(self) -> Result<()> {
+        if self.steps.is_empty() {
+            return self.rollback();
+        }
+
+        let last_path = get_last_journal_path_from(&self.path);
+        remove_last_record(&last_path)?;
+        fs::rename(get_journal_dir(&self.path), get_journal_dir(&last_path))?;
+        fs::rename(&self.path, &last_path)?;
+
+        // the other changed repos point to the record, so it's found by undo on any of them
+        for repo in self.get_repos() {
+            if repo == self.repo {
+                continue;
+            }
+            let link_path = get_last_journal_path(&repo);
+            remove_last_record(&link_path)?;
+            self.make_link(&repo, link_path).save()?;
+
+            let pending_link_path = get_journal_path(&repo);
+            if load_journal(pending_link_path.clone(), &repo)?.is_some_and(|x| x.is_link_to(&self))
+            {
+                fs::remove_file(pending_link_path)?;
+            }
+        }
+        Ok(())
+    }
+
+    // Removes the journal with the saved files
+    fn discard(self) -> Result<()> {
+        fs::remove_dir_all(get_journal_dir(&self.path))?;
+        fs::remove_file(&self.path)?;
+        Ok(())
+    }
+
+    // Rolls back the steps of the operation in the reverse order, and removes the journal
+    // with the links to it. The planned steps are rolled back only as far as they were made
+    pub fn rollback(mut self) -> Result<()> {
+        let link_paths = self.get_link_paths()?;
+        let journal_dir = get_journal_dir(&self.path);
+        while let Some(entry) = self.steps.pop() {
+            log::debug!("Rolling back {entry:?}");
+            rollback_step(&entry, &journal_dir)
+                .with_context(|| format!("Failed to roll back '{}'", entry.step))?;
+            // keep the rest of the steps, in case we fail in the middle
+            self.save()?;
+        }
+        for link_path in link_paths {
+            fs::remove_file(link_path)?;
+        }
+        self.discard()
+    }
+
+    // Gets the paths of the links to this journal kept with the other repos
+    fn get_link_paths(&self) -> Result<Vec<String>> {
+        let mut link_paths = vec![];
+        for repo in self.get_repos() {
+            if repo == self.repo {
+                continue;
+            }
+            for link_path in [get_journal_path(&repo), get_last_journal_path(&repo)] {
+                if load_journal(link_path.clone(), &repo)?.is_some_and(|x| x.is_link_to(self)) {
+                    link_paths.push(link_path);
+                }
+            }
+        }
+        Ok(link_paths)
+    }
+
+    fn make_link(&self, repo_db_path: &str, link_path: String) -> Self {
+        Self {
+            command: self.command.clone(),
+            pid: self.pid,
+            timestamp: self.timestamp,
+            steps: vec![],
+            linked_repos: vec![],
+            record_repo: Some(self.repo.clone()),
+            repo: repo_db_path.to_owned(),
+            path: link_path,
+        }
+    }
+
+    fn is_link_to(&self, record: &Self) -> bool {
+        self.record_repo.as_deref() == Some(record.repo.as_str())
+            && self.command == record.command
+            && self.pid == record.pid
+            && self.timestamp == record.timestamp
+    }
+
+    pub fn rename_file(&mut self, src_path: &str, dest_path: &str) -> Result<()> {
+        let replaced_name = self.get_replaced_name(dest_path);
+        let replaced_path = self.get_saved_path(replaced_name.as_deref());
+        let step =
+            Step::Rename { src: src_path.to_owned(), dest: dest_path.to_owned(), replaced_name };
+        self.make_step(step, || {
+            save_replaced_file(dest_path, replaced_path.as_deref())?;
+            move_file(src_path, dest_path)
+        })
+    }
+
+    pub fn copy_file(&mut self, src_path: &str, dest_path: &str) -> Result<()> {
+        let replaced_name = self.get_replaced_name(dest_path);
+        let replaced_path = self.get_saved_path(replaced_name.as_deref());
+        let step =
+            Step::Copy { src: src_path.to_owned(), dest: dest_path.to_owned(), replaced_name };
+        self.make_step(step, || {
+            save_replaced_file(dest_path, replaced_path.as_deref())?;
+            fs::copy(src_path, dest_path)?;
+            Ok(())
+        })
+    }
+
+    // Moves the file into the journal dir, so it can be restored
+    pub fn remove_file(&mut self, filepath: &str) -> Result<()> {
+        let filename = Path::new(filepath).file_name().unwrap().to_str().unwrap();
+        let saved_name = format!("{}-{filename}", self.steps.len());
+        let saved_path = format!("{}/{saved_name}", get_journal_dir(&self.path));
+        let step = Step::Remove { path: filepath.to_owned(), saved_name };
+        self.make_step(step, || {
+            // fail the same way as the plain removal of the missing file
+            fs::metadata(filepath)?;
+            move_file(filepath, &saved_path)
+        })
+    }
+
+    pub fn create_dir_all(&mut self, dirpath: &str) -> Result<()> {
+        if Path::new(dirpath).exists() {
+            return Ok(());
+        }
+        let step = Step::CreateDir { path: dirpath.to_owned() };
+        self.make_step(step, || fs::create_dir_all(dirpath).map_err(Into::into))
+    }
+
+    // Saves the current DB files of the repo before they are changed.
+    // Only the first change is recorded, as the rollback restores the state before it
+    pub fn save_repo_db(&mut self, repo_db_path: &str) -> Result<()> {
+        if self
+            .steps
+            .iter()
+            .any(|x| matches!(&x.step, Step::ChangeDb { repo, .. } if repo == repo_db_path))
+        {
+            return Ok(());
+        }
+
+        let saved_name = format!("{}-db", self.steps.len());
+        let saved_dir = format!("{}/{saved_name}", get_journal_dir(&self.path));
+        let step = Step::ChangeDb { repo: repo_db_path.to_owned(), saved_name };
+        self.make_step(step, || {
+            fs::create_dir_all(&saved_dir)?;
+            for db_filepath in get_repo_db_files(repo_db_path)? {
+                let db_filename = Path::new(&db_filepath).file_name().unwrap().to_str().unwrap();
+                copy_db_file(&db_filepath, &format!("{saved_dir}/{db_filename}"))?;
+            }
+            Ok(())
+        })
+    }
+
+    // Gets the name to save the existing destination file under, before it's replaced by the step
+    fn get_replaced_name(&self, dest_path: &str) -> Option<String> {
+        fs::symlink_metadata(dest_path).ok()?;
+        let filename = Path::new(dest_path).file_name()?.to_str()?;
+        Some(format!("{}-{filename}", self.steps.len()))
+    }
+
+    fn get_saved_path(&self, saved_name: Option<&str>) -> Option<String> {
+        saved_name.map(|x| format!("{}/{x}", get_journal_dir(&self.path)))
+    }
+
+    // Records the step as planned, makes it and marks as done
+    fn make_step(&mut self, step: Step, make_fn: impl FnOnce() -> Result<()>) -> Result<()> {
+        self.steps.push(JournalEntry { step, state: StepState::Planned });
+        self.save()?;
+
+        if let Err(step_err) = make_fn() {
+            // clean up whatever was made partially, e.g the copied part of the file
+            let entry = self.steps.pop().unwrap();
+            rollback_step(&entry, &get_journal_dir(&self.path))?;
+            self.save()?;
+            return Err(step_err);
+        }
+
+        self.steps.last_mut().unwrap().state = StepState::Done;
+        self.save()
+    }
+
+    // Writes the journal atomically, so it's never left half-written
+    fn save(&self) -> Result<()> {
+        let tmp_path = format!("{}.tmp", self.path);
+        fs::write(&tmp_path, serde_json::to_string_pretty(self)?)?;
+        fs::rename(&tmp_path, &self.path)
+            .with_context(|| format!("Failed to write journal '{}'", self.path))
+    }
+}
+
+// Rolls back the interrupted operation on the repo, if there is one.
+// Returns true if the operation was rolled back. The operation kept with the other repo
+// must be rolled back from there first
+pub fn recover(repo_db_path: &str) -> Result<bool> {
+    let Some(journal) = Journal::load(repo_db_path)? else {
+        return Ok(false);
+    };
+    if let Some(record_repo) = &journal.record_repo {
+        if get_interrupted_record_repo(repo_db_path)?.is_some() {
+            anyhow::bail!(
+                "Interrupted '{}' operation on repo '{repo_db_path}' is kept with repo \
+                 '{record_repo}', it must be rolled back from there",
+                journal.command
+            );
+        }
+        // the operation was finished or rolled back before the link was removed
+        fs::remove_file(&journal.path)?;
+        return Ok(false);
+    }
+    log::warn!(
+        "Found interrupted '{}' operation (process {}) on repo '{repo_db_path}', rolling it back",
+        journal.command,
+        journal.pid
+    );
+    journal.rollback()?;
+    log::info!("Interrupted operation on repo '{repo_db_path}' is rolled back");
+    Ok(true)
+}
+
+// Gets the repo keeping the journal of the interrupted operation, which changed this repo too
+pub fn get_interrupted_record_repo(repo_db_path: &str) -> Result<Option<String>> {
+    let Some(link) = Journal::load(repo_db_path)? else {
+        return Ok(None);
+    };
+    let Some(record_repo) = &link.record_repo else {
+        return Ok(None);
+    };
+    let is_interrupted = Journal::load(record_repo)?.is_some_and(|x| link.is_link_to(&x));
+    Ok(is_interrupted.then(|| record_repo.clone()))
+}
+
+// Gets the path of the journal file next to the repo DB, hidden the same way as the lock file
+pub fn get_journal_path(repo_db_path: &str) -> String {
+    let repo_dir = Path::new(repo_db_path).parent().unwrap();
+    let repo_db_prefix = pkg_utils::get_repo_db_prefix(repo_db_path);
+    format!("{}/.{repo_db_prefix}.journal", repo_dir.to_str().unwrap())
+}
+
+// Gets the path of the record of the last finished operation on the repo
+pub fn get_last_journal_path(repo_db_path: &str) -> String {
+    get_last_journal_path_from(&get_journal_path(repo_db_path))
+}
+
+fn get_last_journal_path_from(journal_path: &str) -> String {
+    format!("{journal_path}.last")
+}
+
+// Gets the dir where the files removed by the operation are kept
+fn get_journal_dir(journal_path: &str) -> String {
+    format!("{journal_path}.d")
+}
+
+// Removes the record of the last operation with its saved files
+fn remove_last_record(last_path: &str) -> Result<()> {
+    if Path::new(last_path).exists() {
+        fs::remove_file(last_path)?;
+    }
+    if Path::new(&get_journal_dir(last_path)).exists() {
+        fs::remove_dir_all(get_journal_dir(last_path))?;
+    }
+    Ok(())
+}
+
+fn load_journal(path: String, repo_db_path: &str) -> Result<Option<Journal>> {
+    if !Path::new(&path).exists() {
+        return Ok(None);
+    }
+    let content = fs::read_to_string(&path)?;
+    let journal: Journal = serde_json::from_str(&content)
+        .with_context(|| format!("Failed to parse journal '{path}'"))?;
+    Ok(Some(Journal { repo: repo_db_path.to_owned(), path, ..journal }))
+}
+
+fn rollback_step(entry: &JournalEntry, journal_dir: &str) -> Result<()> {
+    let is_done = entry.state == StepState::Done;
+    match &entry.step {
+        Step::Rename { src, dest, replaced_name } => {
+            let replaced_path = replaced_name.as_ref().map(|x| format!("{journal_dir}/{x}"));
+            if is_dest_made(dest, replaced_path.as_deref()) {
+                // the planned move might have stopped after copying the file to another filesystem
+                if is_done || !Path::new(src).exists() {
+                    move_file(dest, src)?;
+                } else {
+                    fs::remove_file(dest)?;
+                }
+            }
+            restore_replaced_file(dest, replaced_path.as_deref())?;
+        },
+        Step::Copy { dest, replaced_name, .. } => {
+            let replaced_path = replaced_name.as_ref().map(|x| format!("{journal_dir}/{x}"));
+            if is_dest_made(dest, replaced_path.as_deref()) {
+                fs::remove_file(dest)?;
+            }
+            restore_replaced_file(dest, replaced_path.as_deref())?;
+        },
+        Step::Remove { path, saved_name } => {
+            let saved_path = format!("{journal_dir}/{saved_name}");
+            if Path::new(&saved_path).exists() && !Path::new(path).exists() {
+                move_file(&saved_path, path)?;
+            }
+        },
+        Step::CreateDir { path } => {
+            // keep the dir if anything else was put there
+            if Path::new(path).exists() {
+                if let Err(rm_err) = fs::remove_dir(path) {
+                    log::warn!("Failed to remove dir '{path}': {rm_err}");
+                }
+            }
+        },
+        Step::ChangeDb { repo, saved_name } => {
+            // the DB wasn't changed yet, if the snapshot wasn't finished
+            if is_done {
+                let saved_dir = format!("{journal_dir}/{saved_name}");
+                for db_filepath in get_repo_db_files(repo)? {
+                    fs::remove_file(db_filepath)?;
+                }
+                let repo_dir = Path::new(repo).parent().unwrap().to_str().unwrap();
+                for saved_entry in fs::read_dir(&saved_dir)? {
+                    let saved_path = saved_entry?.path();
+                    let db_filename = saved_path.file_name().unwrap().to_str().unwrap();
+                    copy_db_file(
+                        saved_path.to_str().unwrap(),
+                        &format!("{repo_dir}/{db_filename}"),
+                    )?;
+                }
+            }
+        },
+    }
+    Ok(())
+}
+
+// Checks if the destination file was made by the step. The file at the destination is the one
+// from before the step, if it was going to be replaced, but wasn't saved yet
+fn is_dest_made(dest_path: &str, replaced_path: Option<&str>) -> bool {
+    fs::symlink_metadata(dest_path).is_ok()
+        && replaced_path.is_none_or(|x| fs::symlink_metadata(x).is_ok())
+}
+
+// Moves the file, which is going to be replaced by the step, into the journal dir
+fn save_replaced_file(dest_path: &str, replaced_path: Option<&str>) -> Result<()> {
+    match replaced_path {
+        Some(replaced_path) => move_file(dest_path, replaced_path),
+        None => Ok(()),
+    }
+}
+
+// Moves the file replaced by the step back from the journal dir
+fn restore_replaced_file(dest_path: &str, replaced_path: Option<&str>) -> Result<()> {
+    match replaced_path {
+        Some(replaced_path) if fs::symlink_metadata(replaced_path).is_ok() => {
+            move_file(replaced_path, dest_path)
+        },
+        _ => Ok(()),
+    }
+}
+
+// Gets the DB files of the repo, e.g 'repof.db', 'repof.db.tar.zst', 'repof.files.tar.zst.sig'
+fn get_repo_db_files(repo_db_path: &str) -> Result<Vec<String>> {
+    let repo_dir = Path::new(repo_db_path).parent().unwrap();
+    let repo_db_prefix = pkg_utils::get_repo_db_prefix(repo_db_path);
+    let repo_db_pattern =
+        format!("{}/{repo_db_prefix}.*", glob::Pattern::escape(repo_dir.to_str().unwrap()));
+
+    let mut db_files = vec![];
+    for entry in glob::glob(&repo_db_pattern)? {
+        db_files.push(entry?.to_str().unwrap().to_owned());
+    }
+    Ok(db_files)
+}
+
+// Copies the DB file, keeping the symlinks (e.g 'repof.db' -> 'repof.db.tar.zst') as they are
+fn copy_db_file(src_path: &str, dest_path: &str) -> Result<()> {
+    let metadata = fs::symlink_metadata(src_path)?;
+    if metadata.is_symlink() {
+        std::os::unix::fs::symlink(fs::read_link(src_path)?, dest_path)?;
+    } else {
+        fs::copy(src_path, dest_path)?;
+    }
+    Ok(())
+}
+
+// Moves the file, falling back to copy and remove if it's moved to another filesystem
+fn move_file(src_path: &str, dest_path: &str) -> Result<()> {
+    if fs::rename(src_path, dest_path).is_ok() {
+        return Ok(());
+    }
+    fs::copy(src_path, dest_path)?;
+    fs::remove_file(src_path)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::journal::*;
+    use crate::utils;
+
+    #[test]
+    fn test_get_journal_path() {
+        assert_eq!(
+            get_journal_path("/home/testuser/repos/x86_64/os/repof/repof.db.tar.zst"),
+            "/home/testuser/repos/x86_64/os/repof/.repof.journal"
+        );
+    }
+
+    #[test]
+    fn test_rollback() {
+        let temp_dir = utils::create_temporary_directory(None).unwrap();
+        let repo_db_path = format!("{temp_dir}/repof.db.tar.zst");
+        fs::write(&repo_db_path, "db with dash").unwrap();
+        std::os::unix::fs::symlink("repof.db.tar.zst", format!("{temp_dir}/repof.db")).unwrap();
+        let pkgfile = format!("{temp_dir}/dash-0.5.12-1.1-x86_64.pkg.tar.zst");
+        let outdated_pkgfile = format!("{temp_dir}/dash-0.5.11-1-x86_64.pkg.tar.zst");
+        fs::write(&pkgfile, "dash").unwrap();
+        fs::write(&outdated_pkgfile, "outdated dash").unwrap();
+
+        let mut journal = Journal::begin(&repo_db_path, "move-pkgs").unwrap();
+        journal.create_dir_all(&format!("{temp_dir}/debug")).unwrap();
+        let moved_pkgfile = format!("{temp_dir}/debug/dash-0.5.12-1.1-x86_64.pkg.tar.zst");
+        journal.rename_file(&pkgfile, &moved_pkgfile).unwrap();
+        let copied_pkgfile = format!("{temp_dir}/st-0.8.4-2-x86_64.pkg.tar.zst");
+        journal.copy_file(&moved_pkgfile, &copied_pkgfile).unwrap();
+        journal.remove_file(&outdated_pkgfile).unwrap();
+        assert!(journal.remove_file(&outdated_pkgfile).is_err());
+        journal.save_repo_db(&repo_db_path).unwrap();
+        fs::write(&repo_db_path, "db without dash").unwrap();
+        fs::write(format!("{temp_dir}/repof.files.tar.zst"), "files db").unwrap();
+        journal.save_repo_db(&repo_db_path).unwrap();
+        assert_eq!(journal.steps.len(), 5);
+        assert!(journal.steps.iter().all(|x| x.state == StepState::Done));
+        assert!(!Path::new(&outdated_pkgfile).exists());
+
+        // the journal is persisted, so it can be rolled back by the next run
+        assert_eq!(Journal::load(&repo_db_path).unwrap().as_ref(), Some(&journal));
+        assert!(Journal::begin(&repo_db_path, "update").is_err());
+        drop(journal);
+        assert!(recover(&repo_db_path).unwrap());
+        assert!(!recover(&repo_db_path).unwrap());
+
+        assert_eq!(fs::read_to_string(&pkgfile).unwrap(), "dash");
+        assert_eq!(fs::read_to_string(&outdated_pkgfile).unwrap(), "outdated dash");
+        assert_eq!(fs::read_to_string(&repo_db_path).unwrap(), "db with dash");
+        assert_eq!(
+            fs::read_link(format!("{temp_dir}/repof.db")).unwrap().to_str(),
+            Some("repof.db.tar.zst")
+        );
+        assert!(!Path::new(&copied_pkgfile).exists());
+        assert!(!Path::new(&format!("{temp_dir}/repof.files.tar.zst")).exists());
+        assert!(!Path::new(&format!("{temp_dir}/debug")).exists());
+        assert!(!Path::new(&get_journal_path(&repo_db_path)).exists());
+
+        fs::remove_dir_all(temp_dir).unwrap();
+    }
+
+    #[test]
+    fn test_rollback_planned_steps() {
+        let temp_dir = utils::create_temporary_directory(None).unwrap();
+        let repo_db_path = format!("{temp_dir}/repof.db.tar.zst");
+        let pkgfile = format!("{temp_dir}/dash-0.5.12-1.1-x86_64.pkg.tar.zst");
+        let moved_pkgfile = format!("{temp_dir}/moved.pkg.tar.zst");
+        fs::write(&pkgfile, "dash").unwrap();
+
+        // the process was killed right after the file was moved
+        let mut journal = Journal::begin(&repo_db_path, "update").unwrap();
+        journal.steps.push(JournalEntry {
+            step: Step::Rename {
+                src: pkgfile.clone(),
+                dest: moved_pkgfile.clone(),
+                replaced_name: None,
+            },
+            state: StepState::Planned,
+        });
+        // and before the DB snapshot was finished
+        journal.steps.push(JournalEntry {
+            step: Step::ChangeDb { repo: repo_db_path.clone(), saved_name: "1-db".to_owned() },
+            state: StepState::Planned,
+        });
+        journal.save().unwrap();
+        fs::rename(&pkgfile, &moved_pkgfile).unwrap();
+        fs::write(&repo_db_path, "db").unwrap();
+
+        assert!(recover(&repo_db_path).unwrap());
+        assert_eq!(fs::read_to_string(&pkgfile).unwrap(), "dash");
+        assert_eq!(fs::read_to_string(&repo_db_path).unwrap(), "db");
+
+        fs::remove_dir_all(temp_dir).unwrap();
+    }
+
+    #[test]
+    fn test_rollback_replaced_files() {
+        let temp_dir = utils::create_temporary_directory(None).unwrap();
+        let repo_db_path = format!("{temp_dir}/repof.db.tar.zst");
+        let backup_dir = format!("{temp_dir}/backup");
+        fs::create_dir(&backup_dir).unwrap();
+        let pkgfile = format!("{temp_dir}/dash-0.5.12-1.1-x86_64.pkg.tar.zst");
+        let ref_pkgfile = format!("{temp_dir}/ref-dash-0.5.12-1.1-x86_64.pkg.tar.zst");
+        let backup_pkgfile = format!("{backup_dir}/dash-0.5.12-1.1-x86_64.pkg.tar.zst");
+        fs::write(&pkgfile, "local dash").unwrap();
+        fs::write(&ref_pkgfile, "ref dash").unwrap();
+        fs::write(&backup_pkgfile, "backup dash").unwrap();
+
+        // the files at the destinations are replaced, and restored on rollback
+        let mut journal = Journal::begin(&repo_db_path, "sync").unwrap();
+        journal.rename_file(&pkgfile, &backup_pkgfile).unwrap();
+        journal.copy_file(&ref_pkgfile, &pkgfile).unwrap();
+        assert_eq!(fs::read_to_string(&backup_pkgfile).unwrap(), "local dash");
+        assert_eq!(fs::read_to_string(&pkgfile).unwrap(), "ref dash");
+
+        // the failed copy restores the replaced file right away
+        let missing_pkgfile = format!("{temp_dir}/missing.pkg.tar.zst");
+        assert!(journal.copy_file(&missing_pkgfile, &pkgfile).is_err());
+        assert_eq!(fs::read_to_string(&pkgfile).unwrap(), "ref dash");
+
+        journal.rollback().unwrap();
+        assert_eq!(fs::read_to_string(&pkgfile).unwrap(), "local dash");
+        assert_eq!(fs::read_to_string(&backup_pkgfile).unwrap(), "backup dash");
+        assert_eq!(fs::read_to_string(&ref_pkgfile).unwrap(), "ref dash");
+
+        // the process was killed before the existing file was saved, so the copy wasn't made
+        let mut journal = Journal::begin(&repo_db_path, "sync").unwrap();
+        journal.steps.push(JournalEntry {
+            step: Step::Copy {
+                src: ref_pkgfile.clone(),
+                dest: pkgfile.clone(),
+                replaced_name: Some("0-dash-0.5.12-1.1-x86_64.pkg.tar.zst".to_owned()),
+            },
+            state: StepState::Planned,
+        });
+        journal.save().unwrap();
+        drop(journal);
+        assert!(recover(&repo_db_path).unwrap());
+        assert_eq!(fs::read_to_string(&pkgfile).unwrap(), "local dash");
+
+        fs::remove_dir_all(temp_dir).unwrap();
+    }
+
+    #[test]
+    fn test_finish() {
+        let temp_dir = utils::create_temporary_directory(None).unwrap();
+        let repo_db_path = format!("{temp_dir}/repof.db.tar.zst");
+        let pkgfile = format!("{temp_dir}/dash-0.5.12-1.1-x86_64.pkg.tar.zst");
+        fs::write(&pkgfile, "dash").unwrap();
+
+        let mut journal = Journal::begin(&repo_db_path, "update").unwrap();
+        journal.remove_file(&pkgfile).unwrap();
+        journal.finish().unwrap();
+        assert!(!Path::new(&pkgfile).exists());
+        assert_eq!(Journal::load(&repo_db_path).unwrap(), None);
+
+        // the operation without changes doesn't replace the record of the last one
+        Journal::begin(&repo_db_path, "sync").unwrap().finish().unwrap();
+        let last_journal = Journal::load_last(&repo_db_path).unwrap().unwrap();
+        assert_eq!(last_journal.command, "update");
+        assert_eq!(last_journal.steps.len(), 1);
+        assert_eq!(last_journal.steps[0].step.to_string(), format!("rm '{pkgfile}'"));
+
+        // the last operation is undone from the record
+        last_journal.rollback().unwrap();
+        assert_eq!(fs::read_to_string(&pkgfile).unwrap(), "dash");
+        assert_eq!(Journal::load_last(&repo_db_path).unwrap(), None);
+        assert_eq!(fs::read_dir(&temp_dir).unwrap().count(), 1);
+
+        fs::remove_dir_all(temp_dir).unwrap();
+    }
+
+    #[test]
+    fn test_undo_move_pkgs() {
+        let temp_dir = utils::create_temporary_directory(None).unwrap();
+        let from_repo_dir = format!("{temp_dir}/testing");
+        let to_repo_dir = format!("{temp_dir}/stable");
+        fs::create_dir(&from_repo_dir).unwrap();
+        fs::create_dir(&to_repo_dir).unwrap();
+        let from_repo_db_path = format!("{from_repo_dir}/testing.db.tar.zst");
+        let to_repo_db_path = format!("{to_repo_dir}/stable.db.tar.zst");
+        fs::write(&from_repo_db_path, "db with dash").unwrap();
+        fs::write(&to_repo_db_path, "db without dash").unwrap();
+        let from_pkgfile = format!("{from_repo_dir}/dash-0.5.12-1.1-x86_64.pkg.tar.zst");
+        let to_pkgfile = format!("{to_repo_dir}/dash-0.5.12-1.1-x86_64.pkg.tar.zst");
+        fs::write(&from_pkgfile, "dash").unwrap();
+
+        let mut journal = Journal::begin(&to_repo_db_path, "move-pkgs").unwrap();
+        journal.save_repo_db(&to_repo_db_path).unwrap();
+        fs::write(&to_repo_db_path, "db with dash").unwrap();
+        journal.rename_file(&from_pkgfile, &to_pkgfile).unwrap();
+        journal.save_repo_db(&from_repo_db_path).unwrap();
+        fs::write(&from_repo_db_path, "db without dash").unwrap();
+        assert_eq!(journal.get_repos(), vec![to_repo_db_path.clone(), from_repo_db_path.clone()]);
+        journal.finish().unwrap();
+
+        // the record kept with the target repo is found from the source repo too
+        let last_journal = Journal::load_last(&to_repo_db_path).unwrap().unwrap();
+        assert_eq!(last_journal.command, "move-pkgs");
+        assert_eq!(Journal::load_last(&from_repo_db_path).unwrap().as_ref(), Some(&last_journal));
+
+        // the move is undone in both repos, and the link is removed
+        Journal::load_last(&from_repo_db_path).unwrap().unwrap().rollback().unwrap();
+        assert_eq!(fs::read_to_string(&from_repo_db_path).unwrap(), "db with dash");
+        assert_eq!(fs::read_to_string(&to_repo_db_path).unwrap(), "db without dash");
+        assert_eq!(fs::read_to_string(&from_pkgfile).unwrap(), "dash");
+        assert!(!Path::new(&to_pkgfile).exists());
+        assert_eq!(Journal::load_last(&to_repo_db_path).unwrap(), None);
+        assert_eq!(Journal::load_last(&from_repo_db_path).unwrap(), None);
+        assert_eq!(fs::read_dir(&from_repo_dir).unwrap().count(), 2);
+        assert_eq!(fs::read_dir(&to_repo_dir).unwrap().count(), 1);
+
+        // the later operation on the source repo replaces the link
+        let mut journal = Journal::begin(&to_repo_db_path, "move-pkgs").unwrap();
+        journal.rename_file(&from_pkgfile, &to_pkgfile).unwrap();
+        journal.save_repo_db(&from_repo_db_path).unwrap();
+        journal.finish().unwrap();
+        let mut journal = Journal::begin(&from_repo_db_path, "update").unwrap();
+        journal.save_repo_db(&from_repo_db_path).unwrap();
+        journal.finish().unwrap();
+        assert_eq!(Journal::load_last(&from_repo_db_path).unwrap().unwrap().command, "update");
+        let last_journal = Journal::load_last(&to_repo_db_path).unwrap().unwrap();
+        assert_eq!(last_journal.command, "move-pkgs");
+        assert_ne!(Journal::load_last(&from_repo_db_path).unwrap().as_ref(), Some(&last_journal));
+
+        fs::remove_dir_all(temp_dir).unwrap();
+    }
+
+    #[test]
+    fn test_recover_linked_repo() {
+        let temp_dir = utils::create_temporary_directory(None).unwrap();
+        let from_repo_dir = format!("{temp_dir}/testing");
+        let to_repo_dir = format!("{temp_dir}/stable");
+        fs::create_dir(&from_repo_dir).unwrap();
+        fs::create_dir(&to_repo_dir).unwrap();
+        let from_repo_db_path = format!("{from_repo_dir}/testing.db.tar.zst");
+        let to_repo_db_path = format!("{to_repo_dir}/stable.db.tar.zst");
+        fs::write(&from_repo_db_path, "db with dash").unwrap();
+        fs::write(&to_repo_db_path, "db without dash").unwrap();
+        let from_pkgfile = format!("{from_repo_dir}/dash-0.5.12-1.1-x86_64.pkg.tar.zst");
+        let to_pkgfile = format!("{to_repo_dir}/dash-0.5.12-1.1-x86_64.pkg.tar.zst");
+        fs::write(&from_pkgfile, "dash").unwrap();
+
+        // the process was killed in the middle of the move, before the source DB was changed
+        let mut journal = Journal::begin(&to_repo_db_path, "move-pkgs").unwrap();
+        journal.link_repo(&from_repo_db_path).unwrap();
+        journal.save_repo_db(&to_repo_db_path).unwrap();
+        fs::write(&to_repo_db_path, "db with dash").unwrap();
+        journal.rename_file(&from_pkgfile, &to_pkgfile).unwrap();
+        drop(journal);
+
+        // the operation is found from the source repo, and rolled back from the target one
+        assert_eq!(
+            get_interrupted_record_repo(&from_repo_db_path).unwrap().as_deref(),
+            Some(to_repo_db_path.as_str())
+        );
+        assert!(recover(&from_repo_db_path).is_err());
+        assert!(recover(&to_repo_db_path).unwrap());
+        assert_eq!(fs::read_to_string(&from_repo_db_path).unwrap(), "db with dash");
+        assert_eq!(fs::read_to_string(&to_repo_db_path).unwrap(), "db without dash");
+        assert_eq!(fs::read_to_string(&from_pkgfile).unwrap(), "dash");
+        assert!(!Path::new(&to_pkgfile).exists());
+        assert_eq!(get_interrupted_record_repo(&from_repo_db_path).unwrap(), None);
+        assert!(!recover(&from_repo_db_path).unwrap());
+        assert_eq!(fs::read_dir(&from_repo_dir).unwrap().count(), 2);
+        assert_eq!(fs::read_dir(&to_repo_dir).unwrap().count(), 1);
+
+        // the link left after the operation was gone is removed
+        let mut journal = Journal::begin(&to_repo_db_path, "move-pkgs").unwrap();
+        journal.link_repo(&from_repo_db_path).unwrap();
+        journal.discard().unwrap();
+        assert_eq!(get_interrupted_record_repo(&from_repo_db_path).unwrap(), None);
+        assert!(!recover(&from_repo_db_path).unwrap());
+        assert_eq!(Journal::load(&from_repo_db_path).unwrap(), None);
+
+        // the finished operation replaces the link to the journal with the link to the record
+        let mut journal = Journal::begin(&to_repo_db_path, "move-pkgs").unwrap();
+        journal.link_repo(&from_repo_db_path).unwrap();
+        journal.rename_file(&from_pkgfile, &to_pkgfile).unwrap();
+        journal.finish().unwrap();
+        assert_eq!(Journal::load(&from_repo_db_path).unwrap(), None);
+        let last_journal = Journal::load_last(&from_repo_db_path).unwrap().unwrap();
+        assert_eq!(last_journal.get_repos(), vec![from_repo_db_path.clone()]);
+        last_journal.rollback().unwrap();
+        assert_eq!(fs::read_to_string(&from_pkgfile).unwrap(), "dash");
+        assert_eq!(fs::read_dir(&from_repo_dir).unwrap().count(), 2);
+
+        fs::remove_dir_all(temp_dir).unwrap();
+    }
+}
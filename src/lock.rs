@@ -0,0 +1,144 @@
+use crate::pkg_utils;
+
+use std::fs::{self, File, OpenOptions, TryLockError};
+use std::io::{Seek, Write};
+use std::path::Path;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use anyhow::{Context, Result};
+
+const LOCK_RETRY_INTERVAL: Duration = Duration::from_millis(200);
+
+/// Exclusive advisory lock of the repo, which is released on drop
+#[derive(Debug)]
+pub struct RepoLock {
+    pub path: String,
+    file: File,
+}
+
+impl RepoLock {
+    // Takes the lock of the repo, waiting for the other process to release it up to the timeout
+    pub fn acquire(repo_db_path: &str, timeout: Duration) -> Result<Self> {
+        let lock_path = get_lock_path(repo_db_path);
+        let mut file = OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .read(true)
+            .write(true)
+            .open(&lock_path)
+            .with_context(|| format!("Failed to open lock file '{lock_path}'"))?;
+
+        let start_time = Instant::now();
+        let mut is_waiting = false;
+        loop {
+            match file.try_lock() {
+                Ok(()) => break,
+                Err(TryLockError::WouldBlock) => {},
+                Err(TryLockError::Error(lock_err)) => {
+                    return Err(lock_err)
+                        .with_context(|| format!("Failed to lock file '{lock_path}'"));
+                },
+            }
+
+            if start_time.elapsed() >= timeout {
+                anyhow::bail!(
+                    "Repo '{repo_db_path}' is locked by {} (lock file '{lock_path}'), gave up \
+                     after waiting {}s",
+                    get_lock_holder(&lock_path),
+                    timeout.as_secs()
+                );
+            }
+            if !is_waiting {
+                log::info!("Waiting for {} to release the lock", get_lock_holder(&lock_path));
+                is_waiting = true;
+            }
+            thread::sleep(LOCK_RETRY_INTERVAL.min(timeout.saturating_sub(start_time.elapsed())));
+        }
+
+        // record the holder of the lock, so the waiting processes can report it
+        file.set_len(0)?;
+        file.rewind()?;
+        writeln!(file, "{}", std::process::id())?;
+        log::debug!("Acquired repo lock '{lock_path}'");
+
+        Ok(Self { path: lock_path, file })
+    }
+}
+
+impl Drop for RepoLock {
+    fn drop(&mut self) {
+        // the lock file itself is kept, removing it would race with the waiting processes
+        if let Err(unlock_err) = self.file.set_len(0).and_then(|()| self.file.unlock()) {
+            log::error!("Failed to release repo lock '{}': {unlock_err}", self.path);
+        }
+    }
+}
+
+// Gets the path of the lock file next to the repo DB.
+// It's hidden, so the repo DB files pattern (e.g on reset) doesn't match it
+pub fn get_lock_path(repo_db_path: &str) -> String {
+    let repo_dir = Path::new(repo_db_path).parent().unwrap();
+    let repo_db_prefix = pkg_utils::get_repo_db_prefix(repo_db_path);
+    format!("{}/.{repo_db_prefix}.lock", repo_dir.to_str().unwrap())
+}
+
+// Describes the process holding the lock, e.g "process 1234 (repo-manage-uti)"
+fn get_lock_holder(lock_path: &str) -> String {
+    let Some(pid) =
+        fs::read_to_string(lock_path).ok().and_then(|content| content.trim().parse::<u32>().ok())
+    else {
+        return "another process".to_owned();
+    };
+
+    match fs::read_to_string(format!("/proc/{pid}/comm")) {
+        Ok(process_name) => format!("process {pid} ({})", process_name.trim()),
+        Err(_) => format!("process {pid}"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::lock::*;
+    use crate::utils;
+
+    #[test]
+    fn test_get_lock_path() {
+        assert_eq!(
+            get_lock_path("/home/testuser/repos/x86_64/os/repof/repof.db.tar.zst"),
+            "/home/testuser/repos/x86_64/os/repof/.repof.lock"
+        );
+    }
+
+    #[test]
+    fn test_repo_lock() {
+        let temp_dir = utils::create_temporary_directory(None).unwrap();
+        let repo_db_path = format!("{temp_dir}/repof.db.tar.zst");
+
+        let repo_lock = RepoLock::acquire(&repo_db_path, Duration::ZERO).unwrap();
+        assert_eq!(
+            fs::read_to_string(&repo_lock.path).unwrap(),
+            format!("{}\n", std::process::id())
+        );
+
+        // the lock is held by the file handle, so the second one is rejected naming the holder
+        let lock_err = RepoLock::acquire(&repo_db_path, Duration::from_millis(300)).unwrap_err();
+        let lock_err = lock_err.to_string();
+        assert!(lock_err.contains(&format!("process {}", std::process::id())), "{lock_err}");
+        assert!(lock_err.contains(&repo_lock.path));
+
+        // released on drop
+        drop(repo_lock);
+        let repo_lock = RepoLock::acquire(&repo_db_path, Duration::ZERO).unwrap();
+
+        // waits for the holder to release the lock
+        let waiting_thread = thread::spawn(move || {
+            RepoLock::acquire(&repo_db_path, Duration::from_secs(10)).map(|_| ())
+        });
+        thread::sleep(Duration::from_millis(300));
+        drop(repo_lock);
+        assert!(waiting_thread.join().unwrap().is_ok());
+
+        fs::remove_dir_all(temp_dir).unwrap();
+    }
+}
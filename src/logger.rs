@@ -1,31 +1,209 @@
-use std::env;
+use crate::config::{LogConfig, LogFormat};
 
-use log::{Metadata, Record};
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::os::unix::net::UnixDatagram;
+use std::sync::Mutex;
+use std::{env, mem};
 
-struct SimpleLogger;
+use anyhow::{Context, Result};
+use chrono::SecondsFormat;
+use log::{Level, Metadata, Record};
 
-static LOGGER: SimpleLogger = SimpleLogger;
+const JOURNALD_SOCKET_PATH: &str = "/run/systemd/journal/socket";
+const SYSLOG_IDENTIFIER: &str = "repo-manage-util";
 
-impl log::Log for SimpleLogger {
-    fn enabled(&self, _: &Metadata) -> bool {
-        true
+struct Logger {
+    state: Mutex<LoggerState>,
+}
+
+struct LoggerState {
+    /// Profile and command of the run, which are added to each record
+    profile: Option<String>,
+    command: Option<String>,
+    console_format: LogFormat,
+    log_file: Option<LogFile>,
+    journald: Option<UnixDatagram>,
+}
+
+static LOGGER: Logger = Logger {
+    state: Mutex::new(LoggerState {
+        profile: None,
+        command: None,
+        console_format: LogFormat::Text,
+        log_file: None,
+        journald: None,
+    }),
+};
+
+/// Log file which is rotated when it exceeds the max size,
+/// e.g 'repo-manage.log' -> 'repo-manage.log.1' -> 'repo-manage.log.2'
+struct LogFile {
+    path: String,
+    file: File,
+    size: u64,
+    format: LogFormat,
+    max_size: u64,
+    max_files: usize,
+}
+
+/// Log record with the fields of the run
+struct LogEntry<'a> {
+    timestamp: String,
+    level: Level,
+    profile: Option<&'a str>,
+    command: Option<&'a str>,
+    message: String,
+}
+
+impl log::Log for Logger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= log::max_level()
     }
 
     fn log(&self, record: &Record) {
-        if self.enabled(record.metadata()) {
-            println!("{} - {}", record.level(), record.args());
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+        let Ok(mut state) = self.state.lock() else {
+            return;
+        };
+
+        let entry = LogEntry {
+            timestamp: chrono::Local::now().to_rfc3339_opts(SecondsFormat::Millis, false),
+            level: record.level(),
+            profile: state.profile.as_deref(),
+            command: state.command.as_deref(),
+            message: record.args().to_string(),
+        };
+        let console_line = entry.format(state.console_format);
+        let file_line = state.log_file.as_ref().map(|x| entry.format(x.format));
+        let journald_message = state.journald.as_ref().map(|_| entry.to_journald_message());
+
+        // stdout is kept for the command results, so they can be piped
+        eprintln!("{console_line}");
+
+        if let (Some(log_file), Some(file_line)) = (state.log_file.as_mut(), file_line) {
+            if let Err(write_err) = log_file.write_line(&file_line) {
+                eprintln!("Failed to write log file '{}': {write_err}", log_file.path);
+            }
+        }
+        if let (Some(journald), Some(journald_message)) = (&state.journald, journald_message) {
+            // the journal might be gone, the console log is still there
+            let _ = journald.send_to(&journald_message, JOURNALD_SOCKET_PATH);
         }
     }
 
     fn flush(&self) {
-        // use std::io::Write;
-        // io::stdout().flush().unwrap();
+        if let Ok(mut state) = self.state.lock() {
+            if let Some(log_file) = state.log_file.as_mut() {
+                let _ = log_file.file.flush();
+            }
+        }
+    }
+}
+
+impl LogEntry<'_> {
+    fn format(&self, log_format: LogFormat) -> String {
+        match log_format {
+            LogFormat::Text => {
+                let run_fields = match (self.profile, self.command) {
+                    (Some(profile), Some(command)) => format!(" [{profile}/{command}]"),
+                    (None, Some(command)) => format!(" [{command}]"),
+                    _ => String::new(),
+                };
+                format!("{} {}{run_fields} - {}", self.timestamp, self.level, self.message)
+            },
+            LogFormat::Json => serde_json::json!({
+                "timestamp": self.timestamp,
+                "level": self.level.as_str(),
+                "profile": self.profile,
+                "command": self.command,
+                "message": self.message,
+            })
+            .to_string(),
+        }
+    }
+
+    // Encodes the record with the native journald protocol
+    fn to_journald_message(&self) -> Vec<u8> {
+        let priority = match self.level {
+            Level::Error => 3,
+            Level::Warn => 4,
+            Level::Info => 6,
+            Level::Debug | Level::Trace => 7,
+        };
+
+        let mut message = vec![];
+        append_journald_field(&mut message, "MESSAGE", &self.message);
+        append_journald_field(&mut message, "PRIORITY", &priority.to_string());
+        append_journald_field(&mut message, "SYSLOG_IDENTIFIER", SYSLOG_IDENTIFIER);
+        if let Some(profile) = self.profile {
+            append_journald_field(&mut message, "REPO_MANAGE_PROFILE", profile);
+        }
+        if let Some(command) = self.command {
+            append_journald_field(&mut message, "REPO_MANAGE_COMMAND", command);
+        }
+        message
+    }
+}
+
+impl LogFile {
+    fn open(path: &str, format: LogFormat, max_size: u64, max_files: usize) -> Result<Self> {
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .with_context(|| format!("Failed to open log file '{path}'"))?;
+        let size = file.metadata()?.len();
+        Ok(Self { path: path.to_owned(), file, size, format, max_size, max_files })
+    }
+
+    fn write_line(&mut self, line: &str) -> io::Result<()> {
+        let line_size = line.len() as u64 + 1;
+        if self.max_size > 0 && self.size > 0 && self.size + line_size > self.max_size {
+            self.rotate()?;
+        }
+        writeln!(self.file, "{line}")?;
+        self.size += line_size;
+        Ok(())
+    }
+
+    // Shifts the rotated files, dropping the oldest one, and starts the new log file
+    fn rotate(&mut self) -> io::Result<()> {
+        if self.max_files > 0 {
+            for file_num in (1..self.max_files).rev() {
+                let rotated_path = format!("{}.{file_num}", self.path);
+                if fs::exists(&rotated_path)? {
+                    fs::rename(&rotated_path, format!("{}.{}", self.path, file_num + 1))?;
+                }
+            }
+            fs::rename(&self.path, format!("{}.1", self.path))?;
+        }
+
+        let file = OpenOptions::new().create(true).write(true).truncate(true).open(&self.path)?;
+        drop(mem::replace(&mut self.file, file));
+        self.size = 0;
+        Ok(())
     }
 }
 
-pub fn init_logger() -> Result<(), log::SetLoggerError> {
+// Appends the field to the journald message, the values with newlines are length-prefixed
+fn append_journald_field(message: &mut Vec<u8>, name: &str, value: &str) {
+    message.extend_from_slice(name.as_bytes());
+    if value.contains('\n') {
+        message.push(b'\n');
+        message.extend_from_slice(&(value.len() as u64).to_le_bytes());
+    } else {
+        message.push(b'=');
+    }
+    message.extend_from_slice(value.as_bytes());
+    message.push(b'\n');
+}
+
+pub fn init_logger(verbose: u8, quiet: u8) -> Result<(), log::SetLoggerError> {
     // set log level
-    let max_log_level = if let Ok(env_log) = env::var("RUST_LOG") {
+    let env_log_level = if let Ok(env_log) = env::var("RUST_LOG") {
         let env_log = env_log.to_lowercase();
         match env_log.as_str() {
             "trace" => log::LevelFilter::Trace,
@@ -37,6 +215,142 @@ pub fn init_logger() -> Result<(), log::SetLoggerError> {
     } else {
         log::LevelFilter::Info
     };
+    let max_log_level = get_log_level(env_log_level, verbose, quiet);
 
     log::set_logger(&LOGGER).map(|()| log::set_max_level(max_log_level))
 }
+
+// Shifts the log level by the -v/-q flags, the errors are always logged
+fn get_log_level(base_level: log::LevelFilter, verbose: u8, quiet: u8) -> log::LevelFilter {
+    let log_levels = [
+        log::LevelFilter::Error,
+        log::LevelFilter::Warn,
+        log::LevelFilter::Info,
+        log::LevelFilter::Debug,
+        log::LevelFilter::Trace,
+    ];
+    let base_index = log_levels.iter().position(|x| *x == base_level).unwrap_or(2);
+    let level_index = (base_index + usize::from(verbose))
+        .saturating_sub(usize::from(quiet))
+        .min(log_levels.len() - 1);
+    log_levels[level_index]
+}
+
+// Sets up the log sinks from the config, once it's loaded
+pub fn configure(log_config: &LogConfig) -> Result<()> {
+    let log_file = match &log_config.file {
+        Some(log_filepath) => Some(LogFile::open(
+            log_filepath,
+            log_config.file_format,
+            log_config.max_file_size,
+            log_config.max_files,
+        )?),
+        None => None,
+    };
+    let journald = if log_config.journald {
+        Some(UnixDatagram::unbound().context("Failed to create journald socket")?)
+    } else {
+        None
+    };
+
+    let mut state = LOGGER.state.lock().unwrap();
+    state.console_format = log_config.format;
+    state.log_file = log_file;
+    state.journald = journald;
+    Ok(())
+}
+
+// Sets the profile and command of the run, which are added to each record
+pub fn set_run_fields(profile: &str, command: &str) {
+    let mut state = LOGGER.state.lock().unwrap();
+    state.profile = Some(profile.to_owned());
+    state.command = Some(command.to_owned());
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::logger::*;
+    use crate::utils;
+
+    fn test_entry() -> LogEntry<'static> {
+        LogEntry {
+            timestamp: "2024-05-01T12:00:00.000+02:00".to_owned(),
+            level: Level::Info,
+            profile: Some("repof"),
+            command: Some("update"),
+            message: "Repo update is done!".to_owned(),
+        }
+    }
+
+    #[test]
+    fn test_format() {
+        let entry = test_entry();
+        assert_eq!(
+            entry.format(LogFormat::Text),
+            "2024-05-01T12:00:00.000+02:00 INFO [repof/update] - Repo update is done!"
+        );
+
+        let json_entry: serde_json::Value =
+            serde_json::from_str(&entry.format(LogFormat::Json)).unwrap();
+        assert_eq!(
+            json_entry,
+            serde_json::json!({
+                "timestamp": "2024-05-01T12:00:00.000+02:00",
+                "level": "INFO",
+                "profile": "repof",
+                "command": "update",
+                "message": "Repo update is done!",
+            })
+        );
+
+        let entry = LogEntry { profile: None, command: None, ..test_entry() };
+        assert_eq!(
+            entry.format(LogFormat::Text),
+            "2024-05-01T12:00:00.000+02:00 INFO - Repo update is done!"
+        );
+    }
+
+    #[test]
+    fn test_get_log_level() {
+        assert_eq!(get_log_level(log::LevelFilter::Info, 0, 0), log::LevelFilter::Info);
+        assert_eq!(get_log_level(log::LevelFilter::Info, 1, 0), log::LevelFilter::Debug);
+        assert_eq!(get_log_level(log::LevelFilter::Info, 5, 0), log::LevelFilter::Trace);
+        assert_eq!(get_log_level(log::LevelFilter::Info, 0, 1), log::LevelFilter::Warn);
+        assert_eq!(get_log_level(log::LevelFilter::Info, 0, 5), log::LevelFilter::Error);
+        assert_eq!(get_log_level(log::LevelFilter::Warn, 2, 1), log::LevelFilter::Info);
+    }
+
+    #[test]
+    fn test_journald_message() {
+        let entry = LogEntry { message: "repo-add output:\nok".to_owned(), ..test_entry() };
+        let mut expected_message = b"MESSAGE\n".to_vec();
+        expected_message.extend_from_slice(&19u64.to_le_bytes());
+        expected_message.extend_from_slice(
+            b"repo-add output:\nok\nPRIORITY=6\nSYSLOG_IDENTIFIER=repo-manage-util\n\
+              REPO_MANAGE_PROFILE=repof\nREPO_MANAGE_COMMAND=update\n",
+        );
+        assert_eq!(entry.to_journald_message(), expected_message);
+    }
+
+    #[test]
+    fn test_log_file_rotation() {
+        let temp_dir = utils::create_temporary_directory(None).unwrap();
+        let log_path = format!("{temp_dir}/repo-manage.log");
+
+        let mut log_file = LogFile::open(&log_path, LogFormat::Text, 10, 2).unwrap();
+        for line in ["first", "second", "third", "fourth"] {
+            log_file.write_line(line).unwrap();
+        }
+        assert_eq!(fs::read_to_string(&log_path).unwrap(), "fourth\n");
+        assert_eq!(fs::read_to_string(format!("{log_path}.1")).unwrap(), "third\n");
+        assert_eq!(fs::read_to_string(format!("{log_path}.2")).unwrap(), "second\n");
+        assert!(!fs::exists(format!("{log_path}.3")).unwrap());
+
+        // the size of the existing file is counted
+        let mut log_file = LogFile::open(&log_path, LogFormat::Text, 20, 2).unwrap();
+        log_file.write_line("fifth").unwrap();
+        assert_eq!(fs::read_to_string(&log_path).unwrap(), "fourth\nfifth\n");
+
+        fs::remove_dir_all(temp_dir).unwrap();
+    }
+}
@@ -1,13 +1,25 @@
 mod alpm_helper;
 mod args;
+mod compression;
 mod config;
+mod context;
+mod download;
+mod journal;
+mod lock;
 mod logger;
 mod pkg_utils;
+mod pkginfo;
+mod prompt;
+mod repo_db;
 mod repo_utils;
+mod report;
+mod sig_utils;
+mod status;
 mod utils;
 
-use std::fs;
 use std::path::Path;
+use std::process::ExitCode;
+use std::time::{Duration, SystemTime};
 
 use anyhow::{Context, Result};
 use args::*;
@@ -25,108 +37,273 @@ fn get_repo_dir_from_profile(profile: &config::Profile) -> &Path {
     Path::new(&profile.repo).parent().unwrap()
 }
 
-fn main() -> Result<()> {
+fn get_repo_db_pattern(profile: &config::Profile) -> String {
+    let repo_dir = get_repo_dir_from_profile(profile);
+    let repo_db_prefix = pkg_utils::get_repo_db_prefix(&profile.repo);
+    format!("{}/{repo_db_prefix}.*", repo_dir.to_str().unwrap())
+}
+
+// Takes the lock of the profile repo for the command changing it, and rolls back the operation
+// interrupted by the previous run. Nothing is changed in dry-run mode, so the lock isn't needed
+fn lock_profile_repo(
+    ctx: &context::Context,
+    profile: &config::Profile,
+) -> Result<Option<lock::RepoLock>> {
+    lock_repo(ctx, &profile.repo, profile.lock_timeout)
+}
+
+fn lock_repo(
+    ctx: &context::Context,
+    repo_db_path: &str,
+    lock_timeout: u64,
+) -> Result<Option<lock::RepoLock>> {
+    if ctx.dry_run {
+        return Ok(None);
+    }
+    let repo_lock = lock::RepoLock::acquire(repo_db_path, Duration::from_secs(lock_timeout))?;
+    // the interrupted operation kept with the other repo changed this repo too,
+    // so it's rolled back first under the lock of that repo
+    if let Some(record_repo) = journal::get_interrupted_record_repo(repo_db_path)? {
+        let _record_repo_lock =
+            lock::RepoLock::acquire(&record_repo, Duration::from_secs(lock_timeout))?;
+        journal::recover(&record_repo).with_context(|| {
+            format!("Failed to roll back interrupted operation on '{record_repo}'")
+        })?;
+    }
+    journal::recover(repo_db_path).with_context(|| {
+        format!("Failed to roll back interrupted operation on '{repo_db_path}'")
+    })?;
+    Ok(Some(repo_lock))
+}
+
+// Takes the locks of the repos changed by the command. The repos are always locked in the same
+// order, so the commands changing several repos (e.g move-pkgs) can't deadlock
+fn lock_repos(
+    ctx: &context::Context,
+    repos: &[String],
+    lock_timeout: u64,
+) -> Result<Vec<Option<lock::RepoLock>>> {
+    let mut repos = repos.to_vec();
+    repos.sort_by_key(|x| lock::get_lock_path(x));
+    repos.dedup();
+    repos.iter().map(|x| lock_repo(ctx, x, lock_timeout)).collect()
+}
+
+// Gets the repos changed by the command on the profile: the profile repo and its separate
+// debug repo, where the debug packages are moved into. The debug repo is locked only if its dir
+// exists, the lock file can't be created otherwise
+fn get_profile_repos(profile: &config::Profile) -> Vec<String> {
+    let mut repos = vec![profile.repo.clone()];
+    repos.extend(
+        profile
+            .get_debug_profile()
+            .filter(|x| get_repo_dir_from_profile(x).exists())
+            .map(|x| x.repo),
+    );
+    repos
+}
+
+// Gets the repos to lock for undo: the profile repo and the other repos changed by its last
+// operation (e.g. by move-pkgs)
+fn get_undo_repos(profile: &config::Profile) -> Result<Vec<String>> {
+    let mut repos = vec![profile.repo.clone()];
+    if let Some(last_journal) = journal::Journal::load_last(&profile.repo)? {
+        repos.extend(last_journal.get_repos());
+    }
+    Ok(repos)
+}
+
+fn main() -> ExitCode {
     let args = Cli::parse();
 
-    // initialize the logger
-    logger::init_logger().expect("Failed to initialize logger");
+    // initialize the logger, the log is written to stderr and the command results to stdout
+    logger::init_logger(args.verbose, args.quiet).expect("Failed to initialize logger");
+    logger::set_run_fields(&args.command.profile_name(), args.command.name());
+
+    match run(&args) {
+        Ok(exit_status) => exit_status.into(),
+        Err(run_err) => {
+            log::error!("{run_err:#}");
+            status::ExitStatus::from_error(&run_err).into()
+        },
+    }
+}
+
+fn run(args: &Cli) -> Result<status::ExitStatus> {
+    let output_format = args.output;
 
     // load config
-    let config_path = config::get_config_path()?;
+    let config_path = config::get_config_path(args.config.as_deref())?;
+    log::debug!("Using config '{config_path}'");
     let config = config::parse_config_file(&config_path)?;
+    logger::configure(&config.log)?;
 
+    let mut ctx = context::Context::new(args.dry_run);
+    if ctx.dry_run {
+        log::info!("Running in dry-run mode, nothing will be changed");
+    }
+
+    let cli_interactive = args.interactive;
     match &args.command {
         Commands::Reset(args) => {
             let profile = get_profile_from_config(&args.profile, &config)?;
+            ctx.set_interactive(cli_interactive || profile.interactive);
             let repo_dir = get_repo_dir_from_profile(profile);
 
-            let repo_db_prefix = pkg_utils::get_repo_db_prefix(&profile.repo);
-            let repo_db_pattern = format!("{}/{repo_db_prefix}.*", repo_dir.to_str().unwrap());
-
+            let repo_db_pattern = get_repo_db_pattern(profile);
             log::debug!("repo db path := {repo_db_pattern}");
 
-            do_repo_reset(profile, &repo_db_pattern, repo_dir)?;
-            // TODO(vnepogodin): handle debug packages
-            // move them to debug folder if is set
+            let _repo_locks = lock_repos(&ctx, &get_profile_repos(profile), profile.lock_timeout)?;
+
+            ctx.run_journaled(&profile.repo, "reset", || {
+                do_repo_reset(&ctx, profile, &repo_db_pattern, repo_dir)
+            })?;
         },
         Commands::Update(args) => {
             let profile = get_profile_from_config(&args.profile, &config)?;
+            ctx.set_interactive(cli_interactive || profile.interactive);
             let repo_dir = get_repo_dir_from_profile(profile);
 
-            do_repo_update(profile, repo_dir)?;
-            // TODO(vnepogodin): handle debug packages
-            // move them to debug folder if is set
+            let _repo_locks = lock_repos(&ctx, &get_profile_repos(profile), profile.lock_timeout)?;
+            ctx.run_journaled(&profile.repo, "update", || do_repo_update(&ctx, profile, repo_dir))?;
         },
         Commands::Sync(args) => {
             let profile = get_profile_from_config(&args.profile, &config)?;
+            ctx.set_interactive(cli_interactive || profile.interactive);
             let repo_dir = get_repo_dir_from_profile(profile);
 
-            do_repo_sync(profile, repo_dir)?;
-            // TODO(vnepogodin): handle debug packages
-            // move them to debug folder if is set
+            let _repo_lock = lock_profile_repo(&ctx, profile)?;
+            ctx.run_journaled(&profile.repo, "sync", || do_repo_sync(&ctx, profile, repo_dir))?;
         },
         Commands::MovePkgsToRepo(args) => {
             let profile = get_profile_from_config(&args.profile, &config)?;
+            ctx.set_interactive(cli_interactive || profile.interactive);
             let repo_dir = get_repo_dir_from_profile(profile);
 
-            do_repo_move_pkgs(profile, repo_dir)?;
+            let _repo_lock = lock_profile_repo(&ctx, profile)?;
+            ctx.run_journaled(&profile.repo, "move-pkgs-to-repo", || {
+                do_repo_move_pkgs(&ctx, profile, repo_dir)
+            })?;
         },
         Commands::IsPkgsUpToDate(args) => {
             let profile = get_profile_from_config(&args.profile, &config)?;
+            ctx.set_interactive(cli_interactive || profile.interactive);
             let repo_dir = get_repo_dir_from_profile(profile);
 
-            do_repo_checkup(profile, repo_dir)?;
+            if !do_repo_checkup(profile, repo_dir, output_format)? {
+                return Ok(status::ExitStatus::DriftDetected);
+            }
+        },
+        Commands::IsDebugPkgsOk(args) => {
+            let profile = get_profile_from_config(&args.profile, &config)?;
+            ctx.set_interactive(cli_interactive || profile.interactive);
+            let repo_dir = get_repo_dir_from_profile(profile);
+
+            let _repo_locks = lock_repos(&ctx, &get_profile_repos(profile), profile.lock_timeout)?;
+            ctx.run_journaled(&profile.repo, "is-debug-pkgs-ok", || {
+                do_debug_packages_retention(&ctx, profile, repo_dir)
+            })?;
         },
         Commands::CleanupBackupDir(args) => {
             let profile = get_profile_from_config(&args.profile, &config)?;
+            ctx.set_interactive(cli_interactive || profile.interactive);
+
+            let _repo_lock = lock_profile_repo(&ctx, profile)?;
+            ctx.run_journaled(&profile.repo, "cleanup-backup-dir", || {
+                do_backup_repo_cleanup(&ctx, profile)
+            })?;
+        },
+        Commands::Undo(args) => {
+            let profile = get_profile_from_config(&args.profile, &config)?;
+            ctx.set_interactive(cli_interactive || profile.interactive);
 
-            do_backup_repo_cleanup(profile)?;
+            let undo_repos = get_undo_repos(profile)?;
+            let _repo_locks = lock_repos(&ctx, &undo_repos, profile.lock_timeout)?;
+            do_repo_undo(&ctx, profile, &undo_repos)?;
+        },
+        Commands::ShowProfile(args) => {
+            let profile = get_profile_from_config(&args.profile, &config)?;
+            print!("{}", profile.render(output_format)?);
         },
         Commands::MovePkgs(args) => {
             let from_profile = get_profile_from_config(&args.from, &config)?;
             let from_repo_dir = get_repo_dir_from_profile(from_profile);
 
             let to_profile = get_profile_from_config(&args.to, &config)?;
+            ctx.set_interactive(cli_interactive || to_profile.interactive);
             let to_repo_dir = get_repo_dir_from_profile(to_profile);
 
-            move_packages_from_repo_to_repo(from_profile, from_repo_dir, to_profile, to_repo_dir)?;
+            // both repos are locked in the same order, so the opposite moves can't deadlock
+            let moved_repos = [from_profile.repo.clone(), to_profile.repo.clone()];
+            let _repo_locks = lock_repos(&ctx, &moved_repos, to_profile.lock_timeout)?;
+
+            ctx.run_journaled(&to_profile.repo, "move-pkgs", || {
+                ctx.link_repo(&from_profile.repo)?;
+                move_packages_from_repo_to_repo(
+                    &ctx,
+                    from_profile,
+                    from_repo_dir,
+                    to_profile,
+                    to_repo_dir,
+                )
+            })?;
         },
     }
 
-    Ok(())
+    Ok(status::ExitStatus::Success)
 }
 
-fn do_repo_reset(profile: &config::Profile, repo_db_pattern: &str, repo_dir: &Path) -> Result<()> {
+fn do_repo_reset(
+    ctx: &context::Context,
+    profile: &config::Profile,
+    repo_db_pattern: &str,
+    repo_dir: &Path,
+) -> Result<()> {
     // Remove db and files
     for pattern in [repo_db_pattern] {
         log::debug!("removing db file '{pattern}'..");
         for entry in glob::glob(pattern)? {
-            fs::remove_file(entry?)?
+            let db_filepath = entry?;
+            let db_filepath = db_filepath.to_str().unwrap();
+            if !ctx.confirm(&format!("Remove DB file '{db_filepath}'?"))? {
+                continue;
+            }
+            ctx.remove_file(db_filepath)?
         }
     }
 
-    let mut pkgs_list = pkg_utils::find_packages_in_dir(repo_dir)?;
+    // move debug packages into the debug repo, before adding packages to the DB
+    do_debug_packages_check(ctx, profile, repo_dir)?;
+
+    let mut pkgs_list = pkg_utils::find_packages_in_dir(repo_dir, &profile.pkg_extensions)?;
     let outdated_pkgs = pkg_utils::get_outdated_pkgs(&pkgs_list);
     pkgs_list.retain(|pkg| !outdated_pkgs.contains(pkg));
 
     // don't insert packages without signature
-    if profile.require_signature {
-        pkg_utils::remove_pkgs_without_sig(&mut pkgs_list);
+    if profile.is_signature_checked() {
+        pkg_utils::remove_pkgs_without_valid_sig(&mut pkgs_list, profile);
     }
 
     // run repo-add
-    repo_utils::handle_repo_add(profile, &pkgs_list)?;
+    repo_utils::handle_repo_add(ctx, profile, &pkgs_list)?;
 
     // handle removal/backup here
-    handle_outdated_pkgs(profile, &outdated_pkgs)?;
+    handle_outdated_pkgs(ctx, profile, &outdated_pkgs)?;
 
     log::info!("Repo reset is done!");
 
     Ok(())
 }
 
-fn do_repo_update(profile: &config::Profile, repo_dir: &Path) -> Result<()> {
-    let pkgs_list = pkg_utils::find_packages_in_dir(repo_dir)?;
+fn do_repo_update(
+    ctx: &context::Context,
+    profile: &config::Profile,
+    repo_dir: &Path,
+) -> Result<()> {
+    // move debug packages into the debug repo, before adding packages to the DB
+    do_debug_packages_check(ctx, profile, repo_dir)?;
+
+    let pkgs_list = pkg_utils::find_packages_in_dir(repo_dir, &profile.pkg_extensions)?;
     let outdated_pkgs = pkg_utils::get_outdated_pkgs(&pkgs_list);
     let mut new_pkgs = pkg_utils::get_new_pkgs(&pkgs_list);
 
@@ -135,14 +312,15 @@ fn do_repo_update(profile: &config::Profile, repo_dir: &Path) -> Result<()> {
     // TODO(vnepogodin): handle ref repo updates here
 
     // handle new packages which are not present in the DB
-    let mut brand_new_pkgs = alpm_helper::get_brand_new_packages(&profile.repo)
-        .context("Failed to get brand new pkgs")?;
+    let mut brand_new_pkgs =
+        alpm_helper::get_brand_new_packages(&profile.repo, &profile.pkg_extensions)
+            .context("Failed to get brand new pkgs")?;
     // after append the brand_new_pkgs becomes invalidated (e.g empty Vec)
     new_pkgs.append(&mut brand_new_pkgs);
 
     // don't insert packages without signature
-    if profile.require_signature {
-        pkg_utils::remove_pkgs_without_sig(&mut new_pkgs);
+    if profile.is_signature_checked() {
+        pkg_utils::remove_pkgs_without_valid_sig(&mut new_pkgs, profile);
     }
 
     // if update available then update the DB accordingly
@@ -151,11 +329,11 @@ fn do_repo_update(profile: &config::Profile, repo_dir: &Path) -> Result<()> {
         // TODO(vnepogodin): print which new packages we add
         // e.g adding new package 'pacman'..
 
-        repo_utils::handle_repo_add(profile, &new_pkgs)?;
+        repo_utils::handle_repo_add(ctx, profile, &new_pkgs)?;
 
         // 1.1 handle removal/backup of old packages here
         // NOTE: we are likely to handle it equally for update and reset. lets hope so?
-        handle_outdated_pkgs(profile, &outdated_pkgs)?;
+        handle_outdated_pkgs(ctx, profile, &outdated_pkgs)?;
     }
 
     // 2. handle stale packages
@@ -165,7 +343,7 @@ fn do_repo_update(profile: &config::Profile, repo_dir: &Path) -> Result<()> {
     // if we found stale packages then remove them from DB
     // overwise silently skip and finish update command
     if !stale_pkgs.is_empty() {
-        repo_utils::handle_repo_remove(profile, &stale_pkgs)?;
+        repo_utils::handle_repo_remove(ctx, profile, &stale_pkgs)?;
     }
 
     log::info!("Repo update is done!");
@@ -173,61 +351,146 @@ fn do_repo_update(profile: &config::Profile, repo_dir: &Path) -> Result<()> {
     Ok(())
 }
 
-fn do_repo_sync(profile: &config::Profile, repo_dir: &Path) -> Result<()> {
-    if profile.reference_repo.is_none() {
-        log::error!("Reference repository is not configured. Cannot proceed further");
-        return Ok(());
+fn do_repo_sync(ctx: &context::Context, profile: &config::Profile, repo_dir: &Path) -> Result<()> {
+    let reference_repos = profile.get_reference_repos();
+    if reference_repos.is_empty() {
+        anyhow::bail!("Reference repository is not configured. Cannot proceed further");
     }
 
-    let reference_repo_path = profile.reference_repo.as_ref().unwrap();
-    let packages_to_copy =
-        alpm_helper::get_newer_packages_from_reference(&profile.repo, reference_repo_path)
+    // 1. find packages to copy from the ref repos
+    let newer_packages =
+        alpm_helper::get_newer_packages_from_reference(&profile.repo, &reference_repos)
             .context("Failed to get newer packages from reference repo")?;
-
-    if !packages_to_copy.is_empty() {
-        // NOTE: probably we would rather want here to see filenames instead of full paths
-        log::info!("Found newer packages in ref repo: {packages_to_copy:?}");
+    for newer_pkg in &newer_packages {
+        log::info!(
+            "Found newer package in ref repo '{}': {}",
+            newer_pkg.reference_repo,
+            newer_pkg.filepath
+        );
+    }
+    let newer_pkgs_count = newer_packages.len();
+    let mut packages_to_copy = newer_packages.into_iter().map(|x| x.filepath).collect::<Vec<_>>();
+
+    // packages which exist only in the ref repos
+    if profile.sync_new.enabled {
+        let new_packages =
+            alpm_helper::get_new_packages_from_reference(&profile.repo, &reference_repos)
+                .context("Failed to get new packages from reference repo")?;
+        for new_pkg in new_packages.into_iter().filter(|x| profile.sync_new.is_allowed(&x.name)) {
+            log::info!(
+                "Found new package in ref repo '{}': {}",
+                new_pkg.reference_repo,
+                new_pkg.filepath
+            );
+            packages_to_copy.push(new_pkg.filepath);
+        }
     }
 
-    // lets invalidate packages if they are without signatures
-    if !pkg_utils::validate_packages(profile.require_signature, &packages_to_copy) {
-        log::error!("Aborting due to found 'invalid' packages. Cannot proceed further");
-        return Ok(());
+    // download only the packages to copy from the remote ref repo,
+    // after that they are verified and copied as the local ones
+    let download_dir = download::DownloadDir::new()?;
+    let packages_to_copy = download::fetch_packages(&packages_to_copy, &download_dir.path)?;
+
+    // 2. verify packages, lets invalidate packages if they are without signatures
+    if !pkg_utils::validate_packages(profile, &packages_to_copy) {
+        anyhow::bail!(status::ValidationError(
+            "Aborting due to found 'invalid' packages. Cannot proceed further".to_owned()
+        ));
     }
 
-    // Copy the packages to the profile repository directory
+    // 3. copy the packages to the profile repository directory
     for package_path in &packages_to_copy {
         let ref_pkg = pkg_utils::get_pkg_db_pair_from_path(package_path);
         log::info!("ref repo: {ref_pkg}");
 
-        if let Err(pkg_copy_err) = handle_pkgfile_copy(package_path, repo_dir.to_str().unwrap()) {
-            log::error!("Error occurred while copying package files: {pkg_copy_err}");
-            return Ok(());
+        handle_pkgfile_copy(ctx, package_path, repo_dir.to_str().unwrap())
+            .context("Error occurred while copying package files")?;
+    }
+
+    // move copied debug packages into the debug repo, they don't go into the repo DB
+    let debug_pkgs = match profile.get_debug_profile() {
+        Some(_) => pkg_utils::get_debug_packages(&packages_to_copy),
+        None => vec![],
+    };
+    do_debug_packages_check(ctx, profile, repo_dir)?;
+
+    // 4. add copied packages to the DB
+    let copied_pkgs =
+        packages_to_copy.iter().filter(|x| !debug_pkgs.contains(x)).cloned().collect::<Vec<_>>();
+    let added_pkgs = pkg_utils::replace_base_dir_for_pkgs(&copied_pkgs, repo_dir);
+    if !added_pkgs.is_empty() {
+        repo_utils::handle_repo_add(ctx, profile, &added_pkgs)?;
+    }
+
+    // 5. handle local versions superseded by the copied packages.
+    // In dry-run mode the packages are not copied, so they are compared from the ref repo
+    let mut pkgs_list = pkg_utils::find_packages_in_dir(repo_dir, &profile.pkg_extensions)?;
+    if ctx.dry_run {
+        pkgs_list.extend(copied_pkgs.iter().cloned());
+    }
+    let added_pkgnames = copied_pkgs
+        .iter()
+        .map(|x| pkg_utils::read_pkg_metadata(x).map(|pkginfo| pkginfo.name))
+        .collect::<Result<Vec<_>>>()
+        .context("Failed to read metadata of copied packages")?;
+    let outdated_pkgs = pkg_utils::get_outdated_pkgs_by_name(&pkgs_list, &added_pkgnames);
+    handle_outdated_pkgs(ctx, profile, &outdated_pkgs)?;
+
+    // 6. packages which no longer exist in the ref repo are retired as the outdated ones
+    let mut removed_pkgs_count = 0;
+    if profile.sync_removed.enabled {
+        let removed_packages =
+            alpm_helper::get_removed_packages_from_reference(&profile.repo, &reference_repos)
+                .context("Failed to get removed packages from reference repo")?;
+        let (removed_pkgnames, removed_pkgfiles): (Vec<_>, Vec<_>) = removed_packages
+            .into_iter()
+            .filter(|(pkgname, _)| profile.sync_removed.is_allowed(pkgname))
+            .unzip();
+
+        if !removed_pkgnames.is_empty() {
+            log::info!("Found packages removed from ref repo: {removed_pkgnames:?}");
+            repo_utils::handle_repo_remove(ctx, profile, &removed_pkgnames)?;
+
+            let removed_pkgfiles =
+                removed_pkgfiles.into_iter().filter(|x| Path::new(x).exists()).collect::<Vec<_>>();
+            handle_outdated_pkgs(ctx, profile, &removed_pkgfiles)?;
         }
+        removed_pkgs_count = removed_pkgnames.len();
     }
 
-    // TODO: handle new packages(which dont exist in repo, but exist in ref repo), handle stale
-    // packages(which no longer exist in ref repo)
-
-    log::info!("Repo ref sync is done!");
+    log::info!(
+        "Repo ref sync is done! Copied {} packages ({newer_pkgs_count} newer, {} new, {} debug), \
+         added {} to the DB, retired {} outdated and {removed_pkgs_count} removed packages",
+        packages_to_copy.len(),
+        packages_to_copy.len() - newer_pkgs_count,
+        debug_pkgs.len(),
+        added_pkgs.len(),
+        outdated_pkgs.len(),
+    );
 
     Ok(())
 }
 
-fn do_repo_move_pkgs(profile: &config::Profile, repo_dir: &Path) -> Result<()> {
+fn do_repo_move_pkgs(
+    ctx: &context::Context,
+    profile: &config::Profile,
+    repo_dir: &Path,
+) -> Result<()> {
     // 1. moving packages from current dir
     let current_dir = std::env::current_dir().context("Failed to get current working dir")?;
 
     // here we get only packages without signature
-    let mut pkg_to_move_list = pkg_utils::find_packages_in_dir(current_dir.as_path())?;
+    let mut pkg_to_move_list =
+        pkg_utils::find_packages_in_dir(current_dir.as_path(), &profile.pkg_extensions)?;
 
     // NOTE: probably we would rather want here to see filenames instead of full paths
     log::info!("Found packages to move in current dir: {pkg_to_move_list:?}");
 
     // lets invalidate packages if they are without signatures
-    if !pkg_utils::validate_packages(profile.require_signature, &pkg_to_move_list) {
-        log::error!("Aborting due to found 'invalid' packages. Cannot proceed further");
-        return Ok(());
+    if !pkg_utils::validate_packages(profile, &pkg_to_move_list) {
+        anyhow::bail!(status::ValidationError(
+            "Aborting due to found 'invalid' packages. Cannot proceed further".to_owned()
+        ));
     }
 
     // lets invalidate packages if they are already in the target repo (and are not newer versions)
@@ -240,129 +503,309 @@ fn do_repo_move_pkgs(profile: &config::Profile, repo_dir: &Path) -> Result<()> {
         pkg_to_move_list.retain(|pkg| !already_in_repo.contains(pkg));
     }
 
-    if let Err(pkg_move_err) = handle_pkgfiles_move(&pkg_to_move_list, repo_dir.to_str().unwrap()) {
-        log::error!("Error occurred while moving package files: {pkg_move_err}");
-        return Ok(());
+    handle_pkgfiles_move(ctx, &pkg_to_move_list, repo_dir.to_str().unwrap())
+        .context("Error occurred while moving package files")?;
+
+    // in dry-run mode the packages are not moved, so the repo update wouldn't see them
+    if ctx.dry_run {
+        let moved_pkgs = pkg_utils::replace_base_dir_for_pkgs(&pkg_to_move_list, repo_dir);
+        repo_utils::handle_repo_add(ctx, profile, &moved_pkgs)?;
     }
 
     // 2. doing regular repo update
     // TODO(vnepogodin): don't parse all packages in the repo,
     // we need to touch only packages which we move into
-    do_repo_update(profile, repo_dir)?;
+    do_repo_update(ctx, profile, repo_dir)?;
 
     log::info!("Repo MovePkgsToRepo is done!");
 
     Ok(())
 }
 
-fn do_repo_checkup(profile: &config::Profile, repo_dir: &Path) -> Result<()> {
-    let pkgs_list = pkg_utils::find_packages_in_dir(repo_dir)?;
+// Reports the state of the repo, returns false if it isn't up-to-date
+fn do_repo_checkup(
+    profile: &config::Profile,
+    repo_dir: &Path,
+    output_format: report::OutputFormat,
+) -> Result<bool> {
+    let pkgs_list = pkg_utils::find_packages_in_dir(repo_dir, &profile.pkg_extensions)?;
 
     let outdated_pkgs = pkg_utils::get_outdated_pkgs(&pkgs_list);
     let new_pkgs = pkg_utils::get_new_pkgs(&pkgs_list);
 
     let repo_db_prefix = pkg_utils::get_repo_db_prefix(&profile.repo);
+    let to_entries = |pkg_list: &[String]| {
+        pkg_list
+            .iter()
+            .map(|x| report::PkgEntry::from_pkgfile(x, &repo_db_prefix))
+            .collect::<Result<Vec<_>>>()
+    };
+    let mut checkup_report =
+        report::CheckupReport { repo: repo_db_prefix.clone(), ..Default::default() };
 
     // 1. handle new packages
 
     // handle new packages which are not present in the DB
-    let brand_new_pkgs = alpm_helper::get_brand_new_packages(&profile.repo)
-        .context("Failed to get brand new pkgs")?;
-
-    for brand_new_pkg in brand_new_pkgs {
-        let pkg_pair = pkg_utils::get_pkg_db_pair_from_path(&brand_new_pkg);
-        log::info!("Found brand new package in repo '{repo_db_prefix}': '{pkg_pair}'");
-    }
-
-    for new_pkg in new_pkgs {
-        let pkg_pair = pkg_utils::get_pkg_db_pair_from_path(&new_pkg);
-        log::info!("Found new package in repo '{repo_db_prefix}': '{pkg_pair}'");
-    }
+    let brand_new_pkgs =
+        alpm_helper::get_brand_new_packages(&profile.repo, &profile.pkg_extensions)
+            .context("Failed to get brand new pkgs")?;
+    checkup_report.brand_new = to_entries(&brand_new_pkgs)?;
+    checkup_report.new = to_entries(&new_pkgs)?;
 
     // 1.1 handle removal/backup of old packages here
-    for outdated_pkg in outdated_pkgs {
-        let pkg_pair = pkg_utils::get_pkg_db_pair_from_path(&outdated_pkg);
-        log::info!("Found outdated package in repo '{repo_db_prefix}': '{pkg_pair}'");
-    }
+    checkup_report.outdated = to_entries(&outdated_pkgs)?;
 
     // 2. handle stale packages
 
-    // we want to get here filenames of stale packages
-    let stale_filenames = alpm_helper::get_stale_filenames(&profile.repo)
+    // the files of stale packages don't exist, so they are reported from the DB records
+    let stale_pkgs = alpm_helper::get_stale_db_pkgs(&profile.repo)
         .context("Failed to get stale pkgs with filename")?;
-
-    for stale_filename in stale_filenames {
-        let pkg_pair = pkg_utils::get_pkg_db_pair_from_path(&stale_filename);
-        log::info!("Found stale package in repo '{repo_db_prefix}': '{pkg_pair}'");
+    checkup_report.stale = stale_pkgs
+        .iter()
+        .map(|x| {
+            report::PkgEntry::from_db_record(&x.name, &x.version, &x.filename, &repo_db_prefix)
+        })
+        .collect();
+
+    // 3. check that files DB is consistent with the repo DB
+    match repo_db::check_files_db(&profile.repo) {
+        Ok(mismatches) => {
+            for mismatch in mismatches {
+                log::warn!("Found files DB mismatch in repo '{repo_db_prefix}': {mismatch}");
+            }
+        },
+        Err(check_err) => {
+            log::warn!("Failed to check files DB of repo '{repo_db_prefix}': {check_err}")
+        },
     }
 
-    // 3. handle ref repository
+    // 4. handle ref repository
     // Check for newer packages in the reference repository
-    if let Some(reference_repo_path) = &profile.reference_repo {
+    let reference_repos = profile.get_reference_repos();
+    if !reference_repos.is_empty() {
         let packages_to_copy =
-            alpm_helper::get_newer_packages_from_reference(&profile.repo, reference_repo_path)
+            alpm_helper::get_newer_packages_from_reference(&profile.repo, &reference_repos)
                 .context("Failed to get newer packages from reference repo")?;
+        checkup_report.newer_in_reference = packages_to_copy
+            .iter()
+            .map(|x| {
+                report::PkgEntry::from_db_record(
+                    &x.name,
+                    &x.version,
+                    &x.filepath,
+                    &x.reference_repo,
+                )
+            })
+            .collect();
+    }
+
+    print!("{}", checkup_report.render(output_format)?);
+
+    log::info!("Repo checkup is done!");
 
-        if !packages_to_copy.is_empty() {
-            let new_pkgname_list = packages_to_copy
-                .iter()
-                .map(|x| pkg_utils::get_pkg_db_pair_from_path(x))
-                .collect::<Vec<_>>();
-            log::info!("Found new pkgs from ref repo '{repo_db_prefix}': {new_pkgname_list:?}");
+    Ok(checkup_report.is_up_to_date())
+}
+
+// Undoes the last operation on the repo from its journal, restoring the DB and moving the files
+// back in the reverse order. All repos changed by the operation must be locked
+fn do_repo_undo(
+    ctx: &context::Context,
+    profile: &config::Profile,
+    locked_repos: &[String],
+) -> Result<()> {
+    let Some(last_journal) = journal::Journal::load_last(&profile.repo)? else {
+        log::info!("Nothing to undo for repo '{}'", profile.repo);
+        return Ok(());
+    };
+    // the last operation was replaced while waiting for the locks
+    if let Some(repo) = last_journal.get_repos().iter().find(|x| !locked_repos.contains(x)) {
+        anyhow::bail!("Repo '{repo}' changed by the last operation isn't locked, retry the undo");
+    }
+    // the DB restored by the undo would drop the later changes of the other repo
+    for repo in last_journal.get_repos() {
+        if journal::Journal::load_last(&repo)?.as_ref() != Some(&last_journal) {
+            anyhow::bail!(
+                "Repo '{repo}' was changed after the last '{}' operation, it can't be undone",
+                last_journal.command
+            );
         }
     }
 
-    log::info!("Repo checkup is done!");
+    let elapsed_secs = SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .map(|x| x.as_secs().saturating_sub(last_journal.timestamp))
+        .unwrap_or_default();
+    log::info!(
+        "Last operation on repo '{}' is '{}', made {elapsed_secs}s ago with {} steps",
+        profile.repo,
+        last_journal.command,
+        last_journal.steps.len()
+    );
+    for entry in last_journal.steps.iter().rev() {
+        if ctx.dry_run {
+            println!("[dry-run] undo {}", entry.step);
+        } else {
+            log::info!("undo {}", entry.step);
+        }
+    }
+    if ctx.dry_run {
+        return Ok(());
+    }
+
+    if !ctx.confirm(&format!("Undo the last '{}' operation?", last_journal.command))? {
+        return Ok(());
+    }
+    last_journal.rollback().context("Failed to undo the last operation")?;
+
+    log::info!("Repo undo is done!");
 
     Ok(())
 }
 
-fn do_debug_packages_check(profile: &config::Profile, repo_dir: &Path) -> Result<()> {
+// Moves debug packages from the repo into the debug dir, and updates the separate debug repo DB
+fn do_debug_packages_check(
+    ctx: &context::Context,
+    profile: &config::Profile,
+    repo_dir: &Path,
+) -> Result<()> {
     // 1. check if we have debug repo assigned
-    if profile.debug_dir.is_none() || profile.debug_dir == Some(profile.repo.clone()) {
-        log::info!("Separate debug repo is disabled for this profile");
+    let Some(debug_profile) = profile.get_debug_profile() else {
+        log::debug!("Separate debug repo is disabled for this profile");
         return Ok(());
-    }
-
-    // NOTE: lets just move debug packages into the directory of the repo
-    // don't touch the debug repo DB at all.
+    };
+    let debug_dir = get_repo_dir_from_profile(&debug_profile);
 
     // 2. get all debug packages in the repo it self, to move them into the debug directory
-    let pkgs_list = glob::glob(&format!("{}/*-debug-*.pkg.tar.zst", repo_dir.to_str().unwrap()))?
-        .map(|x| x.unwrap().to_str().unwrap().to_owned())
-        .collect::<Vec<_>>();
-
-    // // the debug_dir is the parent dir without the repo
-    // if let Some(debug_dir) = &profile.debug_dir {
-    //     let debug_pkgs_list =
-    //         glob::glob(&format!("{}/*-debug-*.pkg.tar.zst",
-    // profile.debug_dir.as_ref().unwrap()))?             .map(|x|
-    // x.unwrap().to_str().unwrap().to_owned())             .collect::<Vec<_>>();
-
-    //     pkgs_list.append(&mut debug_pkgs_list);
-    // }
-
-    // TODO(vnepogodin): make a prompt on every run here in case iteractive is on
-    for pkg_to_move in &pkgs_list
-    // .iter().map(|x| Path::new(x))
-    {
+    let pkgs_list = pkg_utils::get_debug_packages(&pkg_utils::find_packages_in_dir(
+        repo_dir,
+        &profile.pkg_extensions,
+    )?);
+
+    if !pkgs_list.is_empty() {
+        ctx.create_dir_all(debug_dir.to_str().unwrap())?;
+    }
+    if debug_dir.exists() {
+        ctx.link_repo(&debug_profile.repo)?;
+    }
+
+    let mut moved_pkgs: Vec<String> = vec![];
+    for pkg_to_move in &pkgs_list {
         let pkg_pair = pkg_utils::get_pkg_db_pair_from_path(pkg_to_move);
         log::debug!("Found debug package in repo: {pkg_pair}");
-        // log::debug!("Moving debug package into debug dir: {pkg_to_move}");
-        // if let Err(file_err) = fs::rename_file(filepath) {
-        //     log::error!("Failed to move the debug package '{filepath}': {file_err}");
-        // }
+        if !ctx.confirm(&format!("Move debug package '{pkg_pair}' into debug dir?"))? {
+            continue;
+        }
+
+        handle_pkgfile_move(ctx, pkg_to_move, debug_dir.to_str().unwrap())
+            .context("Error occurred while moving debug package")?;
+        moved_pkgs.push(pkg_to_move.clone());
+    }
+
+    // 3. remove moved debug packages from the repo DB, if they were added there before
+    if !moved_pkgs.is_empty() && Path::new(&profile.repo).exists() {
+        let removal_pkgs = alpm_helper::get_packages_from_filepaths(&profile.repo, &moved_pkgs)?;
+        if !removal_pkgs.is_empty() {
+            repo_utils::handle_repo_remove(ctx, profile, &removal_pkgs)?;
+        }
+    }
+
+    // in dry-run mode the packages are not moved, so the debug repo update wouldn't see them
+    if ctx.dry_run && !moved_pkgs.is_empty() {
+        let added_pkgs_files = pkg_utils::replace_base_dir_for_pkgs(&moved_pkgs, debug_dir);
+        repo_utils::handle_repo_add(ctx, &debug_profile, &added_pkgs_files)?;
+    }
+
+    // 4. update the debug repo DB with the same outdated/backup handling as the main repo.
+    // The debug repo DB is created from all packages in the debug dir, if it doesn't exist yet
+    if !debug_dir.exists() {
+        return Ok(());
+    }
+    if Path::new(&debug_profile.repo).exists() {
+        do_repo_update(ctx, &debug_profile, debug_dir)?;
+    } else {
+        do_repo_reset(ctx, &debug_profile, &get_repo_db_pattern(&debug_profile), debug_dir)?;
     }
 
     Ok(())
 }
 
+// Prunes debug packages in the debug repo, which are not kept by the retention policy
+fn do_debug_packages_retention(
+    ctx: &context::Context,
+    profile: &config::Profile,
+    repo_dir: &Path,
+) -> Result<()> {
+    let Some(debug_profile) = profile.get_debug_profile() else {
+        log::info!("Separate debug repo is disabled for this profile");
+        return Ok(());
+    };
+    let debug_dir = get_repo_dir_from_profile(&debug_profile);
+    if !debug_dir.exists() {
+        log::info!("Debug repo dir '{}' doesn't exist yet", debug_dir.display());
+        return Ok(());
+    }
+    ctx.link_repo(&debug_profile.repo)?;
+
+    // debug packages are matched against the latest versions in the main repo
+    let mut main_pkgs_list = pkg_utils::find_packages_in_dir(repo_dir, &profile.pkg_extensions)?;
+    pkg_utils::remove_outdated_pkgs(&mut main_pkgs_list);
+    let debug_pkgs_list = pkg_utils::get_debug_packages(&pkg_utils::find_packages_in_dir(
+        debug_dir,
+        &profile.pkg_extensions,
+    )?);
+
+    let pruned_pkgs = pkg_utils::get_pruned_debug_pkgs(
+        &debug_pkgs_list,
+        &main_pkgs_list,
+        profile.debug_keep_num,
+        profile.debug_keep_matching,
+    );
+    if pruned_pkgs.is_empty() {
+        log::info!("Debug packages are OK!");
+        return Ok(());
+    }
+
+    let mut removed_pkgs: Vec<String> = vec![];
+    for (pkg_to_remove, prune_reason) in pruned_pkgs {
+        let pkg_pair = pkg_utils::get_pkg_db_pair_from_path(&pkg_to_remove);
+        log::info!("Found debug package to prune '{pkg_pair}': {prune_reason}");
+        if !ctx.confirm(&format!("Remove debug package '{pkg_pair}'?"))? {
+            continue;
+        }
+
+        ctx.remove_file(&pkg_to_remove)
+            .with_context(|| format!("Failed to remove the debug package '{pkg_to_remove}'"))?;
+
+        // remove package signature
+        let sig_filepath = format!("{pkg_to_remove}.sig");
+        if Path::new(&sig_filepath).exists() {
+            ctx.remove_file(&sig_filepath).with_context(|| {
+                format!("Failed to remove the debug package sig '{sig_filepath}'")
+            })?;
+        }
+        removed_pkgs.push(pkg_to_remove);
+    }
+
+    // remove pruned packages from the debug repo DB
+    if !removed_pkgs.is_empty() && Path::new(&debug_profile.repo).exists() {
+        let removal_pkgs =
+            alpm_helper::get_packages_from_filepaths(&debug_profile.repo, &removed_pkgs)?;
+        if !removal_pkgs.is_empty() {
+            repo_utils::handle_repo_remove(ctx, &debug_profile, &removal_pkgs)?;
+        }
+    }
+
+    log::info!("The debug packages retention is done!");
+
+    Ok(())
+}
+
 // Runs through the backup folder, and removes the backup of versions which we don't want to keep
-fn do_backup_repo_cleanup(profile: &config::Profile) -> Result<()> {
-    if !profile.backup || profile.backup_dir == Some(profile.repo.clone()) {
+fn do_backup_repo_cleanup(ctx: &context::Context, profile: &config::Profile) -> Result<()> {
+    let Some(backup_dir) = profile.get_backup_dir() else {
         log::info!("Backup is disabled for this repo");
         return Ok(());
-    }
+    };
 
     if profile.backup_num.is_none() {
         log::info!(
@@ -373,8 +816,8 @@ fn do_backup_repo_cleanup(profile: &config::Profile) -> Result<()> {
     }
 
     // lets get all packages in the repo it self and the debug repo folder
-    let backup_dir = Path::new(profile.backup_dir.as_ref().unwrap());
-    let pkgs_list = pkg_utils::find_packages_in_dir(backup_dir)?;
+    let pkgs_list =
+        pkg_utils::find_packages_in_dir(Path::new(backup_dir), &profile.pkg_extensions)?;
 
     let mut pkg_map =
         pkg_utils::get_stale_pkg_versions(&pkgs_list, *profile.backup_num.as_ref().unwrap());
@@ -383,23 +826,22 @@ fn do_backup_repo_cleanup(profile: &config::Profile) -> Result<()> {
         let pkg_versions = versions.iter().map(|x| x.1.to_string()).collect::<Vec<_>>();
         log::info!("Found more backup versions of package({name}) than allowed: {pkg_versions:?}");
 
-        // TODO(vnepogodin): make a prompt on every run here in case iteractive is on
         for filepath in versions.iter().map(|x| &x.0) {
+            if !ctx.confirm(&format!("Remove backup package '{filepath}'?"))? {
+                continue;
+            }
             log::debug!("Removing package version: {filepath}");
 
             // remove the actual package file
-            if let Err(file_err) = fs::remove_file(filepath) {
-                log::error!("Failed to remove the backup file '{filepath}': {file_err}");
-            }
+            ctx.remove_file(filepath)
+                .with_context(|| format!("Failed to remove the backup file '{filepath}'"))?;
 
             // remove package signature
             let sig_filepath = format!("{filepath}.sig");
             if Path::new(&sig_filepath).exists() {
-                if let Err(file_err) = fs::remove_file(&sig_filepath) {
-                    log::error!(
-                        "Failed to remove the backup file sig '{sig_filepath}': {file_err}"
-                    );
-                }
+                ctx.remove_file(&sig_filepath).with_context(|| {
+                    format!("Failed to remove the backup file sig '{sig_filepath}'")
+                })?;
             }
         }
     }
@@ -414,68 +856,73 @@ fn do_backup_repo_cleanup(profile: &config::Profile) -> Result<()> {
 // 2. removes packages from the src repo DB
 // 3. adds packages to the dest repo DB
 fn move_packages_from_repo_to_repo(
+    ctx: &context::Context,
     src_profile: &Profile,
     src_repo_dir: &Path,
     dest_profile: &Profile,
     dest_repo_dir: &Path,
 ) -> Result<()> {
-    // here we get only packages without signature
-    let pkg_to_move_list = pkg_utils::find_packages_in_dir(src_repo_dir)?;
+    // here we get only packages without signature,
+    // which are allowed in the dest repo
+    let pkg_to_move_list =
+        pkg_utils::find_packages_in_dir(src_repo_dir, &dest_profile.pkg_extensions)?;
 
     // NOTE: probably we would rather want here to see filenames instead of full paths
     log::info!("Found packages to move in src dir: {pkg_to_move_list:?}");
 
     // lets invalidate packages if they are without signatures
-    if !pkg_utils::validate_packages(dest_profile.require_signature, &pkg_to_move_list) {
-        log::error!("Aborting due to found 'invalid' packages. Cannot proceed further");
-        return Ok(());
+    if !pkg_utils::validate_packages(dest_profile, &pkg_to_move_list) {
+        anyhow::bail!(status::ValidationError(
+            "Aborting due to found 'invalid' packages. Cannot proceed further".to_owned()
+        ));
     }
 
-    if let Err(pkg_move_err) =
-        handle_pkgfiles_move(&pkg_to_move_list, dest_repo_dir.to_str().unwrap())
-    {
-        log::error!("Error occurred while moving package files: {pkg_move_err}");
-        return Ok(());
-    }
+    handle_pkgfiles_move(ctx, &pkg_to_move_list, dest_repo_dir.to_str().unwrap())
+        .context("Error occurred while moving package files")?;
 
     // modify source repo DB (e.g remove the moved packages from the db)
     let added_pkgs_files = pkg_utils::replace_base_dir_for_pkgs(&pkg_to_move_list, dest_repo_dir);
     let removal_pkgs =
         alpm_helper::get_packages_from_filepaths(&src_profile.repo, &pkg_to_move_list)?;
 
-    repo_utils::handle_repo_remove(src_profile, &removal_pkgs)?;
-    repo_utils::handle_repo_add(dest_profile, &added_pkgs_files)?;
+    repo_utils::handle_repo_remove(ctx, src_profile, &removal_pkgs)?;
+    repo_utils::handle_repo_add(ctx, dest_profile, &added_pkgs_files)?;
 
     log::info!("Repo MovePkgsFromRepo2Repo is done!");
 
     Ok(())
 }
 
-fn handle_outdated_pkgs(profile: &config::Profile, outdated_pkgs: &[String]) -> Result<()> {
+fn handle_outdated_pkgs(
+    ctx: &context::Context,
+    profile: &config::Profile,
+    outdated_pkgs: &[String],
+) -> Result<()> {
     // 1. handle removal/backup here
     log::debug!("outdated_pkgs := {outdated_pkgs:?}");
     for outdated_pkg in outdated_pkgs {
         let outdated_pkg_entry = pkg_utils::get_pkg_db_pair_from_path(outdated_pkg);
 
-        // TODO(vnepogodin): make a prompt on every run here in case iteractive is on
-        if profile.backup && profile.backup_dir != Some(profile.repo.clone()) {
+        if let Some(backup_dir) = profile.get_backup_dir() {
+            if !ctx.confirm(&format!("Move outdated package '{outdated_pkg_entry}' to backup?"))? {
+                continue;
+            }
             log::info!("backup '{outdated_pkg_entry}'..");
-            handle_pkgfile_move(outdated_pkg, profile.backup_dir.as_ref().unwrap())?;
+            handle_pkgfile_move(ctx, outdated_pkg, backup_dir)?;
         } else {
-            log::info!("rm '{outdated_pkg_entry}'..");
-            // we would rather be fail safe here and just report without *panicing*
-            if let Err(rm_err) = fs::remove_file(outdated_pkg) {
-                log::error!("Failed to remove outdated package '{outdated_pkg}': {rm_err}");
+            if !ctx.confirm(&format!("Remove outdated package '{outdated_pkg_entry}'?"))? {
+                continue;
             }
+            log::info!("rm '{outdated_pkg_entry}'..");
+            ctx.remove_file(outdated_pkg)
+                .with_context(|| format!("Failed to remove outdated package '{outdated_pkg}'"))?;
 
             // remove package signature
             let sig_filepath = format!("{outdated_pkg}.sig");
             if Path::new(&sig_filepath).exists() {
-                if let Err(file_err) = fs::remove_file(&sig_filepath) {
-                    log::error!(
-                        "Failed to remove outdated package sig '{sig_filepath}': {file_err}"
-                    );
-                }
+                ctx.remove_file(&sig_filepath).with_context(|| {
+                    format!("Failed to remove outdated package sig '{sig_filepath}'")
+                })?;
             }
         }
     }
@@ -484,13 +931,13 @@ fn handle_outdated_pkgs(profile: &config::Profile, outdated_pkgs: &[String]) ->
     // to not spam the log with needless run
     if profile.backup {
         // lets run just regular backup cleanup
-        do_backup_repo_cleanup(profile)?;
+        do_backup_repo_cleanup(ctx, profile)?;
     }
 
     Ok(())
 }
 
-fn handle_pkgfile_copy(pkg_to_copy: &str, dest_dir: &str) -> Result<()> {
+fn handle_pkgfile_copy(ctx: &context::Context, pkg_to_copy: &str, dest_dir: &str) -> Result<()> {
     let pkg_filename = Path::new(&pkg_to_copy).file_name().unwrap().to_str().unwrap();
     let dest_path = format!("{}/{pkg_filename}", dest_dir);
 
@@ -499,22 +946,22 @@ fn handle_pkgfile_copy(pkg_to_copy: &str, dest_dir: &str) -> Result<()> {
     log::info!("Copying pkg from '{pkg_to_copy}' -> '{dest_path}'");
 
     // copying package
-    if let Err(copy_err) = fs::copy(pkg_to_copy, &dest_path) {
+    if let Err(copy_err) = ctx.copy_file(pkg_to_copy, &dest_path) {
         anyhow::bail!("Failed to copy pkg: {copy_err}");
     }
     // copying package signature
     let pkg_sig_to_copy = format!("{pkg_to_copy}.sig");
     let sig_dest_path = format!("{dest_path}.sig");
     if Path::new(&pkg_sig_to_copy).exists() {
-        if let Err(copy_err) = fs::copy(pkg_sig_to_copy, &sig_dest_path) {
-            log::error!("Failed to copy pkg signature: {copy_err}");
+        if let Err(copy_err) = ctx.copy_file(&pkg_sig_to_copy, &sig_dest_path) {
+            anyhow::bail!("Failed to copy pkg signature: {copy_err}");
         }
     }
 
     Ok(())
 }
 
-fn handle_pkgfile_move(pkg_to_move: &str, dest_dir: &str) -> Result<()> {
+fn handle_pkgfile_move(ctx: &context::Context, pkg_to_move: &str, dest_dir: &str) -> Result<()> {
     let pkg_filename = Path::new(&pkg_to_move).file_name().unwrap().to_str().unwrap();
     let dest_path = format!("{}/{pkg_filename}", dest_dir);
 
@@ -525,25 +972,29 @@ fn handle_pkgfile_move(pkg_to_move: &str, dest_dir: &str) -> Result<()> {
     // NOTE: maybe we should handle move part better?
 
     // moving package
-    if let Err(move_err) = fs::rename(pkg_to_move, &dest_path) {
+    if let Err(move_err) = ctx.rename_file(pkg_to_move, &dest_path) {
         anyhow::bail!("Failed to move pkg: {move_err}");
     }
     // moving package signature
     let pkg_sig_to_move = format!("{pkg_to_move}.sig");
     let sig_dest_path = format!("{dest_path}.sig");
     if Path::new(&pkg_sig_to_move).exists() {
-        if let Err(move_err) = fs::rename(pkg_sig_to_move, &sig_dest_path) {
-            log::error!("Failed to move pkg signature: {move_err}");
+        if let Err(move_err) = ctx.rename_file(&pkg_sig_to_move, &sig_dest_path) {
+            anyhow::bail!("Failed to move pkg signature: {move_err}");
         }
     }
 
     Ok(())
 }
 
-fn handle_pkgfiles_move(pkg_to_move_list: &[String], dest_dir: &str) -> Result<()> {
+fn handle_pkgfiles_move(
+    ctx: &context::Context,
+    pkg_to_move_list: &[String],
+    dest_dir: &str,
+) -> Result<()> {
     // now lets move
     for pkg_to_move in pkg_to_move_list {
-        handle_pkgfile_move(pkg_to_move, dest_dir)?;
+        handle_pkgfile_move(ctx, pkg_to_move, dest_dir)?;
     }
 
     Ok(())
@@ -1,28 +1,112 @@
-use crate::{alpm_helper, utils};
+use crate::{alpm_helper, config, pkginfo, sig_utils, utils};
 
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
 use std::path::Path;
 
 pub type PackageMap = HashMap<String, Vec<(String, alpm::Version)>>;
 
 pub fn get_debug_packages(pkg_list: &[String]) -> Vec<String> {
     // Identify debug packages from pkg list
-    let mut debug_pkgs: Vec<String> = vec![];
-    for pkg_filepath in pkg_list {
-        let pkg_filename = Path::new(pkg_filepath).file_name().unwrap().to_str().unwrap();
-        let pkg_name = crate::pkg_utils::get_pkgname_from_filename(pkg_filename);
-        if pkg_name.ends_with("-debug") {
-            debug_pkgs.push(pkg_filepath.clone());
-        }
-    }
+    let mut debug_pkgs: Vec<String> = get_pkgs_map(pkg_list)
+        .into_iter()
+        .filter(|(pkg_name, _)| pkg_name.ends_with("-debug"))
+        .flat_map(|(_, versions)| versions.into_iter().map(|x| x.0))
+        .collect();
     debug_pkgs.sort();
 
     debug_pkgs
 }
 
+/// Reason why the debug package is pruned by the retention policy
+#[derive(Debug, PartialEq)]
+pub enum DebugPruneReason {
+    /// There is no main package built from the same pkgbase
+    NoMainPackage,
+    /// Main package exists, but with different version
+    VersionMismatch,
+    /// There are more versions of the debug package than allowed
+    ExceedsKeepNum,
+}
+
+impl fmt::Display for DebugPruneReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::NoMainPackage => write!(f, "no matching main package"),
+            Self::VersionMismatch => write!(f, "version doesn't match the main package"),
+            Self::ExceedsKeepNum => write!(f, "more versions than allowed"),
+        }
+    }
+}
+
+// Gets debug packages which are not kept by the retention policy.
+// Debug packages are matched with the main packages by pkgbase
+// (e.g 'dash-debug' is built together with 'dash')
+pub fn get_pruned_debug_pkgs(
+    debug_pkg_list: &[String],
+    main_pkg_list: &[String],
+    keep_num: Option<usize>,
+    keep_matching_only: bool,
+) -> Vec<(String, DebugPruneReason)> {
+    // pkgbase -> versions of the main packages
+    let mut main_versions: HashMap<String, HashSet<String>> = HashMap::new();
+    for pkg_filepath in main_pkg_list {
+        match read_pkg_metadata(pkg_filepath) {
+            Ok(pkginfo) => {
+                let pkgbase = pkginfo.base.unwrap_or(pkginfo.name);
+                main_versions.entry(pkgbase).or_default().insert(pkginfo.version);
+            },
+            Err(pkg_err) => log::error!("Skipping package '{pkg_filepath}': {pkg_err:#}"),
+        }
+    }
+
+    let mut pruned_pkgs: Vec<(String, DebugPruneReason)> = vec![];
+    for (name, mut versions) in get_pkgs_map(debug_pkg_list) {
+        let pkgbase = name.strip_suffix("-debug").unwrap_or(&name);
+        let Some(pkgbase_versions) = main_versions.get(pkgbase) else {
+            pruned_pkgs
+                .extend(versions.into_iter().map(|x| (x.0, DebugPruneReason::NoMainPackage)));
+            continue;
+        };
+
+        if keep_matching_only {
+            let (matching, mismatched): (Vec<_>, Vec<_>) =
+                versions.into_iter().partition(|x| pkgbase_versions.contains(&x.1.to_string()));
+            pruned_pkgs
+                .extend(mismatched.into_iter().map(|x| (x.0, DebugPruneReason::VersionMismatch)));
+            versions = matching;
+        }
+
+        if let Some(keep_num) = keep_num {
+            if versions.len() > keep_num {
+                // Sort versions in descending order
+                versions.sort_by(|a, b| b.1.vercmp(&a.1));
+                pruned_pkgs.extend(
+                    versions[keep_num..]
+                        .iter()
+                        .map(|x| (x.0.clone(), DebugPruneReason::ExceedsKeepNum)),
+                );
+            }
+        }
+    }
+    pruned_pkgs.sort_by(|a, b| a.0.cmp(&b.0));
+
+    pruned_pkgs
+}
+
 pub fn get_outdated_pkgs(pkg_list: &[String]) -> Vec<String> {
+    get_outdated_pkgs_from_map(get_pkgs_map(pkg_list))
+}
+
+// Gets the outdated versions of the packages with the provided names,
+// the names are taken from the package metadata
+pub fn get_outdated_pkgs_by_name(pkg_list: &[String], pkgnames: &[String]) -> Vec<String> {
     let mut pkg_map = get_pkgs_map(pkg_list);
+    pkg_map.retain(|name, _| pkgnames.contains(name));
+    get_outdated_pkgs_from_map(pkg_map)
+}
 
+fn get_outdated_pkgs_from_map(mut pkg_map: PackageMap) -> Vec<String> {
     // Identify outdated packages for each group
     let mut outdated_pkgs: Vec<String> = vec![];
     for (_name, versions) in pkg_map.iter_mut() {
@@ -39,24 +123,43 @@ pub fn get_outdated_pkgs(pkg_list: &[String]) -> Vec<String> {
     outdated_pkgs
 }
 
-// Invalidate packages if they are without signatures
-pub fn validate_packages(require_signature: bool, pkg_list: &[String]) -> bool {
+// Invalidate packages if they are without valid signatures made by the allowed signers.
+// Signatures are verified against the profile keyring, or the default gpgv keyring if not set
+pub fn validate_packages(profile: &config::Profile, pkg_list: &[String]) -> bool {
     let mut invalid_pkgs: Vec<String> = vec![];
     for pkg in pkg_list {
-        // check for signature if we require it
-        if require_signature && !Path::new(&format!("{pkg}.sig")).exists() {
+        // check for signature if we require it, or only the allowed signers are accepted
+        if !profile.is_signature_checked() {
+            continue;
+        }
+        let sig_status =
+            sig_utils::check_pkg_signer(pkg, profile.keyring.as_deref(), &profile.allowed_signers);
+        if !sig_status.is_valid() {
             let pkg_db_entry = get_pkg_db_pair_from_path(pkg);
-            log::error!("Found package without required signature: '{pkg_db_entry}'");
+            log::error!("Rejecting package '{pkg_db_entry}': {sig_status}");
             invalid_pkgs.push(pkg.clone());
         }
     }
     invalid_pkgs.is_empty()
 }
 
-// Returns found packages in the provided path
-pub fn find_packages_in_dir(source_dir: &Path) -> anyhow::Result<Vec<String>> {
-    let found_pkg_list = glob::glob(&format!("{}/*.pkg.tar.zst", source_dir.to_str().unwrap()))?
+/// Extensions of the package files which are recognized
+pub const PKG_EXTENSIONS: [&str; 6] =
+    [".pkg.tar.zst", ".pkg.tar.xz", ".pkg.tar.gz", ".pkg.tar.bz2", ".pkg.tar.lz4", ".pkg.tar"];
+
+// Checks if the file is the package with one of the allowed extensions
+pub fn is_package_file(filepath: &str, pkg_extensions: &[String]) -> bool {
+    pkg_extensions.iter().any(|pkg_ext| filepath.ends_with(pkg_ext.as_str()))
+}
+
+// Returns found packages in the provided path, which have one of the allowed extensions
+pub fn find_packages_in_dir(
+    source_dir: &Path,
+    pkg_extensions: &[String],
+) -> anyhow::Result<Vec<String>> {
+    let found_pkg_list = glob::glob(&format!("{}/*.pkg.tar*", source_dir.to_str().unwrap()))?
         .map(|x| x.unwrap().to_str().unwrap().to_owned())
+        .filter(|x| is_package_file(x, pkg_extensions))
         .collect::<Vec<_>>();
 
     Ok(found_pkg_list)
@@ -120,66 +223,106 @@ fn get_pkgs_map(pkg_list: &[String]) -> PackageMap {
 
     // Group packages by name and store their versions
     for pkg_filepath in pkg_list {
-        let pkg_filename = Path::new(pkg_filepath).file_name().unwrap().to_str().unwrap();
-        let pkg_name = crate::pkg_utils::get_pkgname_from_filename(pkg_filename);
-        let pkg_version = crate::pkg_utils::get_pkgver_from_filename(pkg_filename);
+        let pkginfo = match read_pkg_metadata(pkg_filepath) {
+            Ok(pkginfo) => pkginfo,
+            Err(pkg_err) => {
+                log::error!("Skipping package '{pkg_filepath}': {pkg_err:#}");
+                continue;
+            },
+        };
 
-        let version = alpm::Version::new(pkg_version);
+        let version = alpm::Version::new(pkginfo.version);
 
-        pkg_map.entry(pkg_name.into()).or_default().push((pkg_filepath.clone(), version));
+        pkg_map.entry(pkginfo.name).or_default().push((pkg_filepath.clone(), version));
     }
     pkg_map
 }
 
-// pub fn get_pkgver_from_filename(filename: &str) -> &str {
-// let range_size = filename.split('-').count();
-// let dropped_range = range_size - 3;
-//
-// let first_pos = filename.match_indices('-').nth(dropped_range - 1).unwrap().0 + 1;
-// let last_pos = filename.match_indices('-').nth(dropped_range + 1).unwrap().0;
-// let last_pos = std::cmp::min(last_pos, filename.len());
-//
-// &filename[first_pos..last_pos]
-// }
-
-pub fn get_pkgname_from_filename(filename: &str) -> &str {
-    let last_pos = filename.match_indices('-').nth_back(2).unwrap().0;
-    &filename[..last_pos]
+// Reads metadata of the package file, the filename is only used for cross-check
+pub fn read_pkg_metadata(pkg_filepath: &str) -> anyhow::Result<pkginfo::PkgInfo> {
+    let pkginfo = pkginfo::read_pkginfo(pkg_filepath)?;
+
+    let pkg_filename = Path::new(pkg_filepath).file_name().unwrap().to_str().unwrap();
+    let filename_parts = (
+        get_pkgname_from_filename(pkg_filename),
+        get_pkgver_from_filename(pkg_filename),
+        get_pkgarch_from_filename(pkg_filename),
+    );
+    if filename_parts != (Some(&pkginfo.name), Some(&pkginfo.version), Some(&pkginfo.arch)) {
+        log::warn!(
+            "Package filename '{pkg_filename}' doesn't match its metadata '{}-{}-{}'",
+            pkginfo.name,
+            pkginfo.version,
+            pkginfo.arch
+        );
+    }
+
+    Ok(pkginfo)
+}
+
+pub fn get_pkgname_from_filename(filename: &str) -> Option<&str> {
+    let last_pos = filename.match_indices('-').nth_back(2)?.0;
+    Some(&filename[..last_pos])
 }
 
-pub fn get_pkgver_from_filename(filename: &str) -> &str {
+pub fn get_pkgver_from_filename(filename: &str) -> Option<&str> {
     let mut rng = filename.match_indices('-');
-    let last_pos = rng.nth_back(0).unwrap().0;
-    let first_pos = rng.nth_back(1).unwrap().0 + 1;
+    let last_pos = rng.nth_back(0)?.0;
+    let first_pos = rng.nth_back(1)?.0 + 1;
+
+    Some(&filename[first_pos..last_pos])
+}
 
-    &filename[first_pos..last_pos]
+pub fn get_pkgarch_from_filename(filename: &str) -> Option<&str> {
+    let first_pos = filename.rfind('-')? + 1;
+    let last_pos = filename.find(".pkg.tar")?;
+    filename.get(first_pos..last_pos)
 }
 
 pub fn get_pkg_db_pair_from_path(file_path: &str) -> String {
     // NOTE: we can do here same as for pkgname and pkgver,
     // and just return &str which points to part of file_path
     let pkg_filename = Path::new(file_path).file_name().unwrap().to_str().unwrap();
-    let pkg_name = get_pkgname_from_filename(pkg_filename);
-    let pkg_ver = get_pkgver_from_filename(pkg_filename);
-
-    format!("{pkg_name}-{pkg_ver}")
+    match (get_pkgname_from_filename(pkg_filename), get_pkgver_from_filename(pkg_filename)) {
+        (Some(pkg_name), Some(pkg_ver)) => format!("{pkg_name}-{pkg_ver}"),
+        // don't fail here, as it is used only for reporting
+        _ => pkg_filename.to_owned(),
+    }
 }
 
 pub fn get_repo_db_prefix(repo_db_filename: &str) -> String {
     let repo_db_prefix =
         Path::new(repo_db_filename).file_stem().unwrap().to_str().unwrap().to_owned();
-    if let Some(strpos) = repo_db_prefix.find(".db") {
-        return utils::string_substr(&repo_db_prefix, 0, strpos).unwrap().into();
+    for db_ext in [".db", ".files"] {
+        if let Some(strpos) = repo_db_prefix.find(db_ext) {
+            return utils::string_substr(&repo_db_prefix, 0, strpos).unwrap().into();
+        }
     }
 
     repo_db_prefix
 }
 
-pub fn remove_pkgs_without_sig(pkgs_list: &mut Vec<String>) {
+// Gets path to the files DB which belongs to the provided repo DB,
+// e.g '/repo/x86_64/repo.db.tar.zst' -> '/repo/x86_64/repo.files.tar.zst'
+pub fn get_repo_files_path(repo_db_path: &str) -> String {
+    let repo_db_filename = Path::new(repo_db_path).file_name().unwrap().to_str().unwrap();
+    let repo_db_prefix = get_repo_db_prefix(repo_db_path);
+    let repo_db_suffix = &repo_db_filename[repo_db_prefix.len()..];
+    let repo_files_filename = repo_db_suffix.replacen(".db", ".files", 1);
+
+    Path::new(repo_db_path)
+        .with_file_name(format!("{repo_db_prefix}{repo_files_filename}"))
+        .to_str()
+        .unwrap()
+        .to_owned()
+}
+
+pub fn remove_pkgs_without_valid_sig(pkgs_list: &mut Vec<String>, profile: &config::Profile) {
     pkgs_list.retain(|pkg| {
-        let pkg_sig_path = format!("{pkg}.sig");
-        if !Path::new(&pkg_sig_path).exists() {
-            log::error!("package doesn't have required signature {pkg}");
+        let sig_status =
+            sig_utils::check_pkg_signer(pkg, profile.keyring.as_deref(), &profile.allowed_signers);
+        if !sig_status.is_valid() {
+            log::error!("Skipping package {pkg}: {sig_status}");
             false
         } else {
             true
@@ -205,101 +348,221 @@ pub fn exclude_existing_pkgs(repo_db_path: &str, pkg_list: &[String]) -> Vec<Str
 #[cfg(test)]
 mod tests {
     use crate::pkg_utils::*;
+    use crate::sig_utils::tests as sig_tests;
     use std::fs;
 
+    fn all_pkg_extensions() -> Vec<String> {
+        PKG_EXTENSIONS.map(String::from).to_vec()
+    }
+
+    // Creates package files at the provided paths, the metadata is taken from the filename
+    fn create_test_pkgs(pkgs_list: &[String]) {
+        for pkg_filepath in pkgs_list {
+            let pkg_path = Path::new(pkg_filepath);
+            let pkg_dir = pkg_path.parent().unwrap().to_str().unwrap();
+            let pkg_filename = pkg_path.file_name().unwrap().to_str().unwrap();
+            utils::create_test_package(
+                pkg_dir,
+                get_pkgname_from_filename(pkg_filename).unwrap(),
+                get_pkgver_from_filename(pkg_filename).unwrap(),
+                get_pkgarch_from_filename(pkg_filename).unwrap(),
+            );
+        }
+    }
+
+    #[test]
+    fn test_get_pruned_debug_pkgs() {
+        let temp_dir = utils::create_temporary_directory(None).expect("Failed to create temp dir");
+
+        let main_pkgs_list: Vec<String> = vec![
+            format!("{temp_dir}/dash-0.5.12-1.1-x86_64.pkg.tar.zst"),
+            format!("{temp_dir}/st-0.8.4-2-x86_64.pkg.tar.zst"),
+        ];
+        let debug_pkgs_list: Vec<String> = vec![
+            format!("{temp_dir}/dash-debug-0.5.11-1-x86_64.pkg.tar.zst"),
+            format!("{temp_dir}/dash-debug-0.5.12-1-x86_64.pkg.tar.zst"),
+            format!("{temp_dir}/dash-debug-0.5.12-1.1-x86_64.pkg.tar.zst"),
+            format!("{temp_dir}/st-debug-0.8.4-2-x86_64.pkg.tar.zst"),
+            format!("{temp_dir}/dwm-debug-6.2-4-x86_64.pkg.tar.zst"),
+        ];
+        create_test_pkgs(&main_pkgs_list);
+        create_test_pkgs(&debug_pkgs_list);
+
+        // debug packages without main package are always pruned
+        assert_eq!(get_pruned_debug_pkgs(&debug_pkgs_list, &main_pkgs_list, None, false), [(
+            debug_pkgs_list[4].clone(),
+            DebugPruneReason::NoMainPackage
+        )]);
+
+        assert_eq!(get_pruned_debug_pkgs(&debug_pkgs_list, &main_pkgs_list, Some(2), false), [
+            (debug_pkgs_list[0].clone(), DebugPruneReason::ExceedsKeepNum),
+            (debug_pkgs_list[4].clone(), DebugPruneReason::NoMainPackage),
+        ]);
+
+        assert_eq!(get_pruned_debug_pkgs(&debug_pkgs_list, &main_pkgs_list, None, true), [
+            (debug_pkgs_list[0].clone(), DebugPruneReason::VersionMismatch),
+            (debug_pkgs_list[1].clone(), DebugPruneReason::VersionMismatch),
+            (debug_pkgs_list[4].clone(), DebugPruneReason::NoMainPackage),
+        ]);
+
+        fs::remove_dir_all(temp_dir).unwrap();
+    }
+
     #[test]
     fn test_pkgver_from_filename() {
         assert_eq!(
             get_pkgver_from_filename("mkinitcpio-nfs-utils-debug-0.3-8.1-x86_64.pkg.tar.zst"),
-            "0.3-8.1"
+            Some("0.3-8.1")
         );
         assert_eq!(
             get_pkgver_from_filename("btrfs-progs-6.5.3-2.1-x86_64.pkg.tar.zst"),
-            "6.5.3-2.1"
+            Some("6.5.3-2.1")
         );
         assert_eq!(
             get_pkgver_from_filename("octopi-dev-0.15.0.r4.b4301d7-1-x86_64.pkg.tar.zst"),
-            "0.15.0.r4.b4301d7-1"
+            Some("0.15.0.r4.b4301d7-1")
         );
         assert_eq!(
             get_pkgver_from_filename(
                 "sayonara-player-git-1.8.0.beta1.r38.g3d444b4a-1-x86_64.pkg.tar.zst"
             ),
-            "1.8.0.beta1.r38.g3d444b4a-1"
+            Some("1.8.0.beta1.r38.g3d444b4a-1")
         );
         assert_eq!(
             get_pkgver_from_filename(
                 "kvmtool-git-3.18.0.r1956.20230916.9cb1b46-1-x86_64.pkg.tar.zst"
             ),
-            "3.18.0.r1956.20230916.9cb1b46-1"
+            Some("3.18.0.r1956.20230916.9cb1b46-1")
         );
         assert_eq!(
             get_pkgver_from_filename(
                 "linux-xanmod-linux-headers-bin-x64v3-6.6.8-1-x86_64.pkg.tar.zst"
             ),
-            "6.6.8-1"
+            Some("6.6.8-1")
         );
         assert_eq!(
             get_pkgver_from_filename("bcachefs-tools-3:1.11.0-1.1-x86_64.pkg.tar.zst"),
-            "3:1.11.0-1.1"
+            Some("3:1.11.0-1.1")
         );
         assert_eq!(
             get_pkgver_from_filename("argon2-20190702-5.1-x86_64.pkg.tar.zst"),
-            "20190702-5.1"
+            Some("20190702-5.1")
+        );
+        assert_eq!(
+            get_pkgver_from_filename("dash-0.5.12-1.1-x86_64.pkg.tar.zst"),
+            Some("0.5.12-1.1")
         );
-        assert_eq!(get_pkgver_from_filename("dash-0.5.12-1.1-x86_64.pkg.tar.zst"), "0.5.12-1.1");
     }
 
     #[test]
     fn test_pkgname_from_filename() {
         assert_eq!(
             get_pkgname_from_filename("mkinitcpio-nfs-utils-debug-0.3-8.1-x86_64.pkg.tar.zst"),
-            "mkinitcpio-nfs-utils-debug"
+            Some("mkinitcpio-nfs-utils-debug")
         );
         assert_eq!(
             get_pkgname_from_filename("btrfs-progs-6.5.3-2.1-x86_64.pkg.tar.zst"),
-            "btrfs-progs"
+            Some("btrfs-progs")
         );
         assert_eq!(
             get_pkgname_from_filename("octopi-dev-0.15.0.r4.b4301d7-1-x86_64.pkg.tar.zst"),
-            "octopi-dev"
+            Some("octopi-dev")
         );
         assert_eq!(
             get_pkgname_from_filename(
                 "sayonara-player-git-1.8.0.beta1.r38.g3d444b4a-1-x86_64.pkg.tar.zst"
             ),
-            "sayonara-player-git"
+            Some("sayonara-player-git")
         );
         assert_eq!(
             get_pkgname_from_filename(
                 "kvmtool-git-3.18.0.r1956.20230916.9cb1b46-1-x86_64.pkg.tar.zst"
             ),
-            "kvmtool-git"
+            Some("kvmtool-git")
         );
         assert_eq!(
             get_pkgname_from_filename(
                 "linux-xanmod-linux-headers-bin-x64v3-6.6.8-1-x86_64.pkg.tar.zst"
             ),
-            "linux-xanmod-linux-headers-bin-x64v3"
+            Some("linux-xanmod-linux-headers-bin-x64v3")
         );
         assert_eq!(
             get_pkgname_from_filename("bcachefs-tools-3:1.11.0-1.1-x86_64.pkg.tar.zst"),
-            "bcachefs-tools"
+            Some("bcachefs-tools")
+        );
+        assert_eq!(
+            get_pkgname_from_filename("argon2-20190702-5.1-x86_64.pkg.tar.zst"),
+            Some("argon2")
         );
-        assert_eq!(get_pkgname_from_filename("argon2-20190702-5.1-x86_64.pkg.tar.zst"), "argon2");
-        assert_eq!(get_pkgname_from_filename("dash-0.5.12-1.1-x86_64.pkg.tar.zst"), "dash");
-        assert_eq!(get_pkgname_from_filename("emacs-29.4-2.1-x86_64.pkg.tar.zst"), "emacs");
+        assert_eq!(get_pkgname_from_filename("dash-0.5.12-1.1-x86_64.pkg.tar.zst"), Some("dash"));
+        assert_eq!(get_pkgname_from_filename("emacs-29.4-2.1-x86_64.pkg.tar.zst"), Some("emacs"));
         assert_eq!(
             get_pkgname_from_filename("emacs-nativecomp-29.4-2.1-x86_64.pkg.tar.zst"),
-            "emacs-nativecomp"
+            Some("emacs-nativecomp")
+        );
+        assert_eq!(
+            get_pkgname_from_filename("emacs-nox-29.4-2.1-x86_64.pkg.tar.zst"),
+            Some("emacs-nox")
         );
-        assert_eq!(get_pkgname_from_filename("emacs-nox-29.4-2.1-x86_64.pkg.tar.zst"), "emacs-nox");
         assert_eq!(
             get_pkgname_from_filename("emacs-wayland-29.4-2.1-x86_64.pkg.tar.zst"),
-            "emacs-wayland"
+            Some("emacs-wayland")
         );
     }
 
+    #[test]
+    fn test_pkgarch_from_filename() {
+        assert_eq!(
+            get_pkgarch_from_filename("mkinitcpio-nfs-utils-debug-0.3-8.1-x86_64.pkg.tar.zst"),
+            Some("x86_64")
+        );
+        assert_eq!(
+            get_pkgarch_from_filename("lightdm-webkit2-theme-arch-1:0.1-1-any.pkg.tar.zst"),
+            Some("any")
+        );
+        assert_eq!(
+            get_pkgarch_from_filename("dash-0.5.12-1.1-x86_64_v3.pkg.tar.xz"),
+            Some("x86_64_v3")
+        );
+    }
+
+    #[test]
+    fn test_malformed_filename() {
+        assert_eq!(get_pkgname_from_filename("dash.pkg.tar.zst"), None);
+        assert_eq!(get_pkgver_from_filename("dash-x86_64.pkg.tar.zst"), None);
+        assert_eq!(get_pkgarch_from_filename("dash-0.5.12-1.1-x86_64"), None);
+        assert_eq!(get_pkg_db_pair_from_path("/to/file/dash.pkg.tar.zst"), "dash.pkg.tar.zst");
+    }
+
+    #[test]
+    fn test_pkgs_map_uses_metadata() {
+        let temp_dir = utils::create_temporary_directory(None).expect("Failed to create temp dir");
+
+        // the filename claims older version than the package actually has
+        let renamed_pkg = utils::create_test_package(&temp_dir, "dash", "0.5.12-2", "x86_64");
+        let misleading_path = format!("{temp_dir}/dash-0.5.12-1-x86_64.pkg.tar.zst");
+        fs::rename(&renamed_pkg, &misleading_path).unwrap();
+        let older_pkg = utils::create_test_package(&temp_dir, "dash", "0.5.12-1.1", "x86_64");
+
+        // unreadable package is skipped instead of panicking
+        let broken_pkg = format!("{temp_dir}/broken.pkg.tar.zst");
+        fs::write(&broken_pkg, b"not a package").unwrap();
+
+        let pkgs_list = vec![misleading_path.clone(), older_pkg.clone(), broken_pkg];
+        assert_eq!(get_outdated_pkgs(&pkgs_list), vec![older_pkg.clone()]);
+        assert_eq!(get_new_pkgs(&pkgs_list), vec![misleading_path]);
+
+        // the packages are matched by the name from the metadata, not from the filename
+        let other_pkg = utils::create_test_package(&temp_dir, "dash-static", "0.5.12-1", "x86_64");
+        let other_path = format!("{temp_dir}/dash-0.5.11-1-x86_64.pkg.tar.zst");
+        fs::rename(&other_pkg, &other_path).unwrap();
+        let pkgs_list = [pkgs_list, vec![other_path]].concat();
+        assert_eq!(get_outdated_pkgs_by_name(&pkgs_list, &["dash".to_owned()]), vec![older_pkg]);
+        assert!(get_outdated_pkgs_by_name(&pkgs_list, &["dash-static".to_owned()]).is_empty());
+
+        fs::remove_dir_all(temp_dir).unwrap();
+    }
+
     #[test]
     fn test_pkg_db_pair_from_path() {
         assert_eq!(
@@ -351,23 +614,37 @@ mod tests {
     #[test]
     fn test_repo_db_prefix() {
         assert_eq!(get_repo_db_prefix("example.db.tar.zst"), "example".to_owned());
-        assert_eq!(get_repo_db_prefix("example.files.tar.zst"), "example.files.tar".to_owned());
+        assert_eq!(get_repo_db_prefix("example.files.tar.zst"), "example".to_owned());
+        assert_eq!(get_repo_db_prefix("/to/repo/example.db"), "example".to_owned());
+    }
+
+    #[test]
+    fn test_repo_files_path() {
+        assert_eq!(
+            get_repo_files_path("/to/repo/example.db.tar.zst"),
+            "/to/repo/example.files.tar.zst".to_owned()
+        );
+        assert_eq!(get_repo_files_path("/to/repo/example.db"), "/to/repo/example.files".to_owned());
+        assert_eq!(get_repo_files_path("example.db.tar.xz"), "example.files.tar.xz".to_owned());
     }
 
     #[test]
     fn test_n_pkg_version() {
+        let temp_dir = utils::create_temporary_directory(None).expect("Failed to create temp dir");
+
         let pkgs_list: Vec<String> = vec![
-            "local_repo/x86_64/bcachefs-tools-3:1.11.0-1.1-x86_64.pkg.tar.zst".into(),
-            "local_repo/x86_64/bcachefs-tools-3:1.11.0-1.2-x86_64.pkg.tar.zst".into(),
-            "local_repo/x86_64/cachyos-cli-installer-new-0.7.0-1-x86_64.pkg.tar.zst".into(),
-            "local_repo/x86_64/cachyos-cli-installer-new-0.7.0-2-x86_64.pkg.tar.zst".into(),
-            "local_repo/x86_64/cachyos-cli-installer-new-0.7.0-3-x86_64.pkg.tar.zst".into(),
+            format!("{temp_dir}/bcachefs-tools-3:1.11.0-1.1-x86_64.pkg.tar.zst"),
+            format!("{temp_dir}/bcachefs-tools-3:1.11.0-1.2-x86_64.pkg.tar.zst"),
+            format!("{temp_dir}/cachyos-cli-installer-new-0.7.0-1-x86_64.pkg.tar.zst"),
+            format!("{temp_dir}/cachyos-cli-installer-new-0.7.0-2-x86_64.pkg.tar.zst"),
+            format!("{temp_dir}/cachyos-cli-installer-new-0.7.0-3-x86_64.pkg.tar.zst"),
         ];
+        create_test_pkgs(&pkgs_list);
         let pkg_version_slice = get_stale_pkg_versions(&pkgs_list, 2);
 
         let expected_version_slice: PackageMap =
             HashMap::from([("cachyos-cli-installer-new".to_string(), vec![(
-                "local_repo/x86_64/cachyos-cli-installer-new-0.7.0-1-x86_64.pkg.tar.zst".into(),
+                format!("{temp_dir}/cachyos-cli-installer-new-0.7.0-1-x86_64.pkg.tar.zst"),
                 alpm::Version::new("0.7.0-1"),
             )])]);
 
@@ -379,188 +656,225 @@ mod tests {
 
         let expected_version_slice = vec![
             ("bcachefs-tools".to_string(), vec![(
-                "local_repo/x86_64/bcachefs-tools-3:1.11.0-1.1-x86_64.pkg.tar.zst".into(),
+                format!("{temp_dir}/bcachefs-tools-3:1.11.0-1.1-x86_64.pkg.tar.zst"),
                 alpm::Version::new("3:1.11.0-1.1"),
             )]),
             ("cachyos-cli-installer-new".to_string(), vec![
                 (
-                    "local_repo/x86_64/cachyos-cli-installer-new-0.7.0-1-x86_64.pkg.tar.zst".into(),
+                    format!("{temp_dir}/cachyos-cli-installer-new-0.7.0-1-x86_64.pkg.tar.zst"),
                     alpm::Version::new("0.7.0-1"),
                 ),
                 (
-                    "local_repo/x86_64/cachyos-cli-installer-new-0.7.0-2-x86_64.pkg.tar.zst".into(),
+                    format!("{temp_dir}/cachyos-cli-installer-new-0.7.0-2-x86_64.pkg.tar.zst"),
                     alpm::Version::new("0.7.0-2"),
                 ),
             ]),
         ];
 
         assert_eq!(pkg_version_slice, expected_version_slice);
+
+        fs::remove_dir_all(temp_dir).unwrap();
     }
 
     #[test]
     fn test_debug_pkgs() {
+        let temp_dir = utils::create_temporary_directory(None).expect("Failed to create temp dir");
+
         let pkgs_list: Vec<String> = vec![
-            "local_repo/x86_64/bcachefs-tools-3:1.11.0-1.1-x86_64.pkg.tar.zst".into(),
-            "local_repo/x86_64/bcachefs-tools-debug-3:1.11.0-1.1-x86_64.pkg.tar.zst".into(),
-            "local_repo/x86_64/cachyos-cli-installer-new-0.7.0-1-x86_64.pkg.tar.zst".into(),
-            "local_repo/x86_64/cachyos-cli-installer-new-0.7.0-2-x86_64.pkg.tar.zst".into(),
-            "local_repo/x86_64/cachyos-cli-installer-new-0.7.0-3-x86_64.pkg.tar.zst".into(),
-            "local_repo/x86_64/dolt-1.30.4-1.1-x86_64.pkg.tar.zst".into(),
-            "local_repo/x86_64/dwl-git-0.2.1.r34.2d9740c-1-x86_64.pkg.tar.zst".into(),
-            "local_repo/x86_64/dwm-6.2-4-x86_64.pkg.tar.zst".into(),
-            "local_repo/x86_64/lightdm-webkit2-theme-arch-1:0.1-1-any.pkg.tar.zst".into(),
-            "local_repo/x86_64/plymouth-theme-hud-3-git-r38.bf2f570-1-any.pkg.tar.zst".into(),
-            "local_repo/x86_64/st-0.8.4-2-x86_64.pkg.tar.zst".into(),
+            format!("{temp_dir}/bcachefs-tools-3:1.11.0-1.1-x86_64.pkg.tar.zst"),
+            format!("{temp_dir}/bcachefs-tools-debug-3:1.11.0-1.1-x86_64.pkg.tar.zst"),
+            format!("{temp_dir}/cachyos-cli-installer-new-0.7.0-1-x86_64.pkg.tar.zst"),
+            format!("{temp_dir}/cachyos-cli-installer-new-0.7.0-2-x86_64.pkg.tar.zst"),
+            format!("{temp_dir}/cachyos-cli-installer-new-0.7.0-3-x86_64.pkg.tar.zst"),
+            format!("{temp_dir}/dolt-1.30.4-1.1-x86_64.pkg.tar.zst"),
+            format!("{temp_dir}/dwl-git-0.2.1.r34.2d9740c-1-x86_64.pkg.tar.zst"),
+            format!("{temp_dir}/dwm-6.2-4-x86_64.pkg.tar.zst"),
+            format!("{temp_dir}/lightdm-webkit2-theme-arch-1:0.1-1-any.pkg.tar.zst"),
+            format!("{temp_dir}/plymouth-theme-hud-3-git-r38.bf2f570-1-any.pkg.tar.zst"),
+            format!("{temp_dir}/st-0.8.4-2-x86_64.pkg.tar.zst"),
         ];
+        create_test_pkgs(&pkgs_list);
         let debug_pkg_list = get_debug_packages(&pkgs_list);
 
         let expected_debug_pkg_list: Vec<String> =
-            vec!["local_repo/x86_64/bcachefs-tools-debug-3:1.11.0-1.1-x86_64.pkg.tar.zst".into()];
+            vec![format!("{temp_dir}/bcachefs-tools-debug-3:1.11.0-1.1-x86_64.pkg.tar.zst")];
 
         assert_eq!(debug_pkg_list, expected_debug_pkg_list);
+
+        fs::remove_dir_all(temp_dir).unwrap();
     }
 
     #[test]
     fn test_outdated_pkgs() {
+        let temp_dir = utils::create_temporary_directory(None).expect("Failed to create temp dir");
+
         let pkgs_list: Vec<String> = vec![
-            "local_repo/x86_64/bcachefs-tools-3:1.11.0-1.1-x86_64.pkg.tar.zst".into(),
-            "local_repo/x86_64/bcachefs-tools-3:1.9.4-1.1-x86_64.pkg.tar.zst".into(),
-            "local_repo/x86_64/cachyos-cli-installer-new-0.7.0-1-x86_64.pkg.tar.zst".into(),
-            "local_repo/x86_64/cachyos-cli-installer-new-0.7.0-2-x86_64.pkg.tar.zst".into(),
-            "local_repo/x86_64/cachyos-cli-installer-new-0.7.0-3-x86_64.pkg.tar.zst".into(),
-            "local_repo/x86_64/dolt-1.30.4-1.1-x86_64.pkg.tar.zst".into(),
-            "local_repo/x86_64/dwl-git-0.2.1.r34.2d9740c-1-x86_64.pkg.tar.zst".into(),
-            "local_repo/x86_64/dwm-6.2-4-x86_64.pkg.tar.zst".into(),
-            "local_repo/x86_64/lightdm-webkit2-theme-arch-1:0.1-1-any.pkg.tar.zst".into(),
-            "local_repo/x86_64/plymouth-theme-hud-3-git-r38.bf2f570-1-any.pkg.tar.zst".into(),
-            "local_repo/x86_64/st-0.8.4-2-x86_64.pkg.tar.zst".into(),
+            format!("{temp_dir}/bcachefs-tools-3:1.11.0-1.1-x86_64.pkg.tar.zst"),
+            format!("{temp_dir}/bcachefs-tools-3:1.9.4-1.1-x86_64.pkg.tar.zst"),
+            format!("{temp_dir}/cachyos-cli-installer-new-0.7.0-1-x86_64.pkg.tar.zst"),
+            format!("{temp_dir}/cachyos-cli-installer-new-0.7.0-2-x86_64.pkg.tar.zst"),
+            format!("{temp_dir}/cachyos-cli-installer-new-0.7.0-3-x86_64.pkg.tar.zst"),
+            format!("{temp_dir}/dolt-1.30.4-1.1-x86_64.pkg.tar.zst"),
+            format!("{temp_dir}/dwl-git-0.2.1.r34.2d9740c-1-x86_64.pkg.tar.zst"),
+            format!("{temp_dir}/dwm-6.2-4-x86_64.pkg.tar.zst"),
+            format!("{temp_dir}/lightdm-webkit2-theme-arch-1:0.1-1-any.pkg.tar.zst"),
+            format!("{temp_dir}/plymouth-theme-hud-3-git-r38.bf2f570-1-any.pkg.tar.zst"),
+            format!("{temp_dir}/st-0.8.4-2-x86_64.pkg.tar.zst"),
         ];
+        create_test_pkgs(&pkgs_list);
         let outdated_list = get_outdated_pkgs(&pkgs_list);
 
         let expected_outdated_list: Vec<String> = vec![
-            "local_repo/x86_64/bcachefs-tools-3:1.9.4-1.1-x86_64.pkg.tar.zst".into(),
-            "local_repo/x86_64/cachyos-cli-installer-new-0.7.0-1-x86_64.pkg.tar.zst".into(),
-            "local_repo/x86_64/cachyos-cli-installer-new-0.7.0-2-x86_64.pkg.tar.zst".into(),
+            format!("{temp_dir}/bcachefs-tools-3:1.9.4-1.1-x86_64.pkg.tar.zst"),
+            format!("{temp_dir}/cachyos-cli-installer-new-0.7.0-1-x86_64.pkg.tar.zst"),
+            format!("{temp_dir}/cachyos-cli-installer-new-0.7.0-2-x86_64.pkg.tar.zst"),
         ];
 
         assert_eq!(outdated_list, expected_outdated_list);
+
+        fs::remove_dir_all(temp_dir).unwrap();
     }
 
     #[test]
     fn test_fresh_pkgs() {
+        let temp_dir = utils::create_temporary_directory(None).expect("Failed to create temp dir");
+
         let pkgs_list: Vec<String> = vec![
-            "local_repo/x86_64/bcachefs-tools-3:1.11.0-1.1-x86_64.pkg.tar.zst".into(),
-            "local_repo/x86_64/cachyos-cli-installer-new-0.7.0-3-x86_64.pkg.tar.zst".into(),
-            "local_repo/x86_64/dolt-1.30.4-1.1-x86_64.pkg.tar.zst".into(),
-            "local_repo/x86_64/dwl-git-0.2.1.r34.2d9740c-1-x86_64.pkg.tar.zst".into(),
-            "local_repo/x86_64/dwm-6.2-4-x86_64.pkg.tar.zst".into(),
-            "local_repo/x86_64/lightdm-webkit2-theme-arch-1:0.1-1-any.pkg.tar.zst".into(),
-            "local_repo/x86_64/plymouth-theme-hud-3-git-r38.bf2f570-1-any.pkg.tar.zst".into(),
-            "local_repo/x86_64/st-0.8.4-2-x86_64.pkg.tar.zst".into(),
+            format!("{temp_dir}/bcachefs-tools-3:1.11.0-1.1-x86_64.pkg.tar.zst"),
+            format!("{temp_dir}/cachyos-cli-installer-new-0.7.0-3-x86_64.pkg.tar.zst"),
+            format!("{temp_dir}/dolt-1.30.4-1.1-x86_64.pkg.tar.zst"),
+            format!("{temp_dir}/dwl-git-0.2.1.r34.2d9740c-1-x86_64.pkg.tar.zst"),
+            format!("{temp_dir}/dwm-6.2-4-x86_64.pkg.tar.zst"),
+            format!("{temp_dir}/lightdm-webkit2-theme-arch-1:0.1-1-any.pkg.tar.zst"),
+            format!("{temp_dir}/plymouth-theme-hud-3-git-r38.bf2f570-1-any.pkg.tar.zst"),
+            format!("{temp_dir}/st-0.8.4-2-x86_64.pkg.tar.zst"),
         ];
+        create_test_pkgs(&pkgs_list);
         let outdated_list = get_outdated_pkgs(&pkgs_list);
 
         let expected_outdated_list: Vec<String> = vec![];
         assert_eq!(outdated_list, expected_outdated_list);
+
+        fs::remove_dir_all(temp_dir).unwrap();
     }
 
     #[test]
     fn test_remove_outdated_pkgs() {
+        let temp_dir = utils::create_temporary_directory(None).expect("Failed to create temp dir");
+
         let mut pkgs_list: Vec<String> = vec![
-            "local_repo/x86_64/bcachefs-tools-3:1.11.0-1.1-x86_64.pkg.tar.zst".into(),
-            "local_repo/x86_64/bcachefs-tools-3:1.9.4-1.1-x86_64.pkg.tar.zst".into(),
-            "local_repo/x86_64/cachyos-cli-installer-new-0.7.0-1-x86_64.pkg.tar.zst".into(),
-            "local_repo/x86_64/cachyos-cli-installer-new-0.7.0-2-x86_64.pkg.tar.zst".into(),
-            "local_repo/x86_64/cachyos-cli-installer-new-0.7.0-3-x86_64.pkg.tar.zst".into(),
-            "local_repo/x86_64/dolt-1.30.4-1.1-x86_64.pkg.tar.zst".into(),
-            "local_repo/x86_64/dwl-git-0.2.1.r34.2d9740c-1-x86_64.pkg.tar.zst".into(),
-            "local_repo/x86_64/dwm-6.2-4-x86_64.pkg.tar.zst".into(),
-            "local_repo/x86_64/lightdm-webkit2-theme-arch-1:0.1-1-any.pkg.tar.zst".into(),
-            "local_repo/x86_64/plymouth-theme-hud-3-git-r38.bf2f570-1-any.pkg.tar.zst".into(),
-            "local_repo/x86_64/st-0.8.4-2-x86_64.pkg.tar.zst".into(),
+            format!("{temp_dir}/bcachefs-tools-3:1.11.0-1.1-x86_64.pkg.tar.zst"),
+            format!("{temp_dir}/bcachefs-tools-3:1.9.4-1.1-x86_64.pkg.tar.zst"),
+            format!("{temp_dir}/cachyos-cli-installer-new-0.7.0-1-x86_64.pkg.tar.zst"),
+            format!("{temp_dir}/cachyos-cli-installer-new-0.7.0-2-x86_64.pkg.tar.zst"),
+            format!("{temp_dir}/cachyos-cli-installer-new-0.7.0-3-x86_64.pkg.tar.zst"),
+            format!("{temp_dir}/dolt-1.30.4-1.1-x86_64.pkg.tar.zst"),
+            format!("{temp_dir}/dwl-git-0.2.1.r34.2d9740c-1-x86_64.pkg.tar.zst"),
+            format!("{temp_dir}/dwm-6.2-4-x86_64.pkg.tar.zst"),
+            format!("{temp_dir}/lightdm-webkit2-theme-arch-1:0.1-1-any.pkg.tar.zst"),
+            format!("{temp_dir}/plymouth-theme-hud-3-git-r38.bf2f570-1-any.pkg.tar.zst"),
+            format!("{temp_dir}/st-0.8.4-2-x86_64.pkg.tar.zst"),
         ];
+        create_test_pkgs(&pkgs_list);
         let expected_pkgs_list: Vec<String> = vec![
-            "local_repo/x86_64/bcachefs-tools-3:1.11.0-1.1-x86_64.pkg.tar.zst".into(),
-            "local_repo/x86_64/cachyos-cli-installer-new-0.7.0-3-x86_64.pkg.tar.zst".into(),
-            "local_repo/x86_64/dolt-1.30.4-1.1-x86_64.pkg.tar.zst".into(),
-            "local_repo/x86_64/dwl-git-0.2.1.r34.2d9740c-1-x86_64.pkg.tar.zst".into(),
-            "local_repo/x86_64/dwm-6.2-4-x86_64.pkg.tar.zst".into(),
-            "local_repo/x86_64/lightdm-webkit2-theme-arch-1:0.1-1-any.pkg.tar.zst".into(),
-            "local_repo/x86_64/plymouth-theme-hud-3-git-r38.bf2f570-1-any.pkg.tar.zst".into(),
-            "local_repo/x86_64/st-0.8.4-2-x86_64.pkg.tar.zst".into(),
+            format!("{temp_dir}/bcachefs-tools-3:1.11.0-1.1-x86_64.pkg.tar.zst"),
+            format!("{temp_dir}/cachyos-cli-installer-new-0.7.0-3-x86_64.pkg.tar.zst"),
+            format!("{temp_dir}/dolt-1.30.4-1.1-x86_64.pkg.tar.zst"),
+            format!("{temp_dir}/dwl-git-0.2.1.r34.2d9740c-1-x86_64.pkg.tar.zst"),
+            format!("{temp_dir}/dwm-6.2-4-x86_64.pkg.tar.zst"),
+            format!("{temp_dir}/lightdm-webkit2-theme-arch-1:0.1-1-any.pkg.tar.zst"),
+            format!("{temp_dir}/plymouth-theme-hud-3-git-r38.bf2f570-1-any.pkg.tar.zst"),
+            format!("{temp_dir}/st-0.8.4-2-x86_64.pkg.tar.zst"),
         ];
 
         remove_outdated_pkgs(&mut pkgs_list);
         assert_eq!(pkgs_list, expected_pkgs_list);
+
+        fs::remove_dir_all(temp_dir).unwrap();
     }
     #[test]
     fn test_remove_empty_outdated_pkgs() {
+        let temp_dir = utils::create_temporary_directory(None).expect("Failed to create temp dir");
+
         let mut pkgs_list: Vec<String> = vec![
-            "local_repo/x86_64/bcachefs-tools-3:1.11.0-1.1-x86_64.pkg.tar.zst".into(),
-            "local_repo/x86_64/cachyos-cli-installer-new-0.7.0-3-x86_64.pkg.tar.zst".into(),
-            "local_repo/x86_64/dolt-1.30.4-1.1-x86_64.pkg.tar.zst".into(),
-            "local_repo/x86_64/dwl-git-0.2.1.r34.2d9740c-1-x86_64.pkg.tar.zst".into(),
-            "local_repo/x86_64/dwm-6.2-4-x86_64.pkg.tar.zst".into(),
-            "local_repo/x86_64/lightdm-webkit2-theme-arch-1:0.1-1-any.pkg.tar.zst".into(),
-            "local_repo/x86_64/plymouth-theme-hud-3-git-r38.bf2f570-1-any.pkg.tar.zst".into(),
-            "local_repo/x86_64/st-0.8.4-2-x86_64.pkg.tar.zst".into(),
+            format!("{temp_dir}/bcachefs-tools-3:1.11.0-1.1-x86_64.pkg.tar.zst"),
+            format!("{temp_dir}/cachyos-cli-installer-new-0.7.0-3-x86_64.pkg.tar.zst"),
+            format!("{temp_dir}/dolt-1.30.4-1.1-x86_64.pkg.tar.zst"),
+            format!("{temp_dir}/dwl-git-0.2.1.r34.2d9740c-1-x86_64.pkg.tar.zst"),
+            format!("{temp_dir}/dwm-6.2-4-x86_64.pkg.tar.zst"),
+            format!("{temp_dir}/lightdm-webkit2-theme-arch-1:0.1-1-any.pkg.tar.zst"),
+            format!("{temp_dir}/plymouth-theme-hud-3-git-r38.bf2f570-1-any.pkg.tar.zst"),
+            format!("{temp_dir}/st-0.8.4-2-x86_64.pkg.tar.zst"),
         ];
+        create_test_pkgs(&pkgs_list);
         let expected_pkgs_list: Vec<String> = vec![
-            "local_repo/x86_64/bcachefs-tools-3:1.11.0-1.1-x86_64.pkg.tar.zst".into(),
-            "local_repo/x86_64/cachyos-cli-installer-new-0.7.0-3-x86_64.pkg.tar.zst".into(),
-            "local_repo/x86_64/dolt-1.30.4-1.1-x86_64.pkg.tar.zst".into(),
-            "local_repo/x86_64/dwl-git-0.2.1.r34.2d9740c-1-x86_64.pkg.tar.zst".into(),
-            "local_repo/x86_64/dwm-6.2-4-x86_64.pkg.tar.zst".into(),
-            "local_repo/x86_64/lightdm-webkit2-theme-arch-1:0.1-1-any.pkg.tar.zst".into(),
-            "local_repo/x86_64/plymouth-theme-hud-3-git-r38.bf2f570-1-any.pkg.tar.zst".into(),
-            "local_repo/x86_64/st-0.8.4-2-x86_64.pkg.tar.zst".into(),
+            format!("{temp_dir}/bcachefs-tools-3:1.11.0-1.1-x86_64.pkg.tar.zst"),
+            format!("{temp_dir}/cachyos-cli-installer-new-0.7.0-3-x86_64.pkg.tar.zst"),
+            format!("{temp_dir}/dolt-1.30.4-1.1-x86_64.pkg.tar.zst"),
+            format!("{temp_dir}/dwl-git-0.2.1.r34.2d9740c-1-x86_64.pkg.tar.zst"),
+            format!("{temp_dir}/dwm-6.2-4-x86_64.pkg.tar.zst"),
+            format!("{temp_dir}/lightdm-webkit2-theme-arch-1:0.1-1-any.pkg.tar.zst"),
+            format!("{temp_dir}/plymouth-theme-hud-3-git-r38.bf2f570-1-any.pkg.tar.zst"),
+            format!("{temp_dir}/st-0.8.4-2-x86_64.pkg.tar.zst"),
         ];
 
         remove_outdated_pkgs(&mut pkgs_list);
         assert_eq!(pkgs_list, expected_pkgs_list);
+
+        fs::remove_dir_all(temp_dir).unwrap();
     }
 
     #[test]
     fn test_new_pkgs() {
+        let temp_dir = utils::create_temporary_directory(None).expect("Failed to create temp dir");
+
         let pkgs_list: Vec<String> = vec![
-            "local_repo/x86_64/bcachefs-tools-3:1.11.0-1.1-x86_64.pkg.tar.zst".into(),
-            "local_repo/x86_64/bcachefs-tools-3:1.9.4-1.1-x86_64.pkg.tar.zst".into(),
-            "local_repo/x86_64/cachyos-cli-installer-new-0.7.0-1-x86_64.pkg.tar.zst".into(),
-            "local_repo/x86_64/cachyos-cli-installer-new-0.7.0-2-x86_64.pkg.tar.zst".into(),
-            "local_repo/x86_64/cachyos-cli-installer-new-0.7.0-3-x86_64.pkg.tar.zst".into(),
-            "local_repo/x86_64/dolt-1.30.4-1.1-x86_64.pkg.tar.zst".into(),
-            "local_repo/x86_64/dwl-git-0.2.1.r34.2d9740c-1-x86_64.pkg.tar.zst".into(),
-            "local_repo/x86_64/dwm-6.2-4-x86_64.pkg.tar.zst".into(),
-            "local_repo/x86_64/lightdm-webkit2-theme-arch-1:0.1-1-any.pkg.tar.zst".into(),
-            "local_repo/x86_64/plymouth-theme-hud-3-git-r38.bf2f570-1-any.pkg.tar.zst".into(),
-            "local_repo/x86_64/st-0.8.4-2-x86_64.pkg.tar.zst".into(),
+            format!("{temp_dir}/bcachefs-tools-3:1.11.0-1.1-x86_64.pkg.tar.zst"),
+            format!("{temp_dir}/bcachefs-tools-3:1.9.4-1.1-x86_64.pkg.tar.zst"),
+            format!("{temp_dir}/cachyos-cli-installer-new-0.7.0-1-x86_64.pkg.tar.zst"),
+            format!("{temp_dir}/cachyos-cli-installer-new-0.7.0-2-x86_64.pkg.tar.zst"),
+            format!("{temp_dir}/cachyos-cli-installer-new-0.7.0-3-x86_64.pkg.tar.zst"),
+            format!("{temp_dir}/dolt-1.30.4-1.1-x86_64.pkg.tar.zst"),
+            format!("{temp_dir}/dwl-git-0.2.1.r34.2d9740c-1-x86_64.pkg.tar.zst"),
+            format!("{temp_dir}/dwm-6.2-4-x86_64.pkg.tar.zst"),
+            format!("{temp_dir}/lightdm-webkit2-theme-arch-1:0.1-1-any.pkg.tar.zst"),
+            format!("{temp_dir}/plymouth-theme-hud-3-git-r38.bf2f570-1-any.pkg.tar.zst"),
+            format!("{temp_dir}/st-0.8.4-2-x86_64.pkg.tar.zst"),
         ];
+        create_test_pkgs(&pkgs_list);
         let new_pkgs_list = get_new_pkgs(&pkgs_list);
 
         let expected_new_pkgs_list: Vec<String> = vec![
-            "local_repo/x86_64/bcachefs-tools-3:1.11.0-1.1-x86_64.pkg.tar.zst".into(),
-            "local_repo/x86_64/cachyos-cli-installer-new-0.7.0-3-x86_64.pkg.tar.zst".into(),
+            format!("{temp_dir}/bcachefs-tools-3:1.11.0-1.1-x86_64.pkg.tar.zst"),
+            format!("{temp_dir}/cachyos-cli-installer-new-0.7.0-3-x86_64.pkg.tar.zst"),
         ];
 
         assert_eq!(new_pkgs_list, expected_new_pkgs_list);
+
+        fs::remove_dir_all(temp_dir).unwrap();
     }
 
     #[test]
     fn test_no_new_pkgs() {
+        let temp_dir = utils::create_temporary_directory(None).expect("Failed to create temp dir");
+
         let pkgs_list: Vec<String> = vec![
-            "local_repo/x86_64/bcachefs-tools-3:1.11.0-1.1-x86_64.pkg.tar.zst".into(),
-            "local_repo/x86_64/cachyos-cli-installer-new-0.7.0-3-x86_64.pkg.tar.zst".into(),
-            "local_repo/x86_64/dolt-1.30.4-1.1-x86_64.pkg.tar.zst".into(),
-            "local_repo/x86_64/dwl-git-0.2.1.r34.2d9740c-1-x86_64.pkg.tar.zst".into(),
-            "local_repo/x86_64/dwm-6.2-4-x86_64.pkg.tar.zst".into(),
-            "local_repo/x86_64/lightdm-webkit2-theme-arch-1:0.1-1-any.pkg.tar.zst".into(),
-            "local_repo/x86_64/plymouth-theme-hud-3-git-r38.bf2f570-1-any.pkg.tar.zst".into(),
-            "local_repo/x86_64/st-0.8.4-2-x86_64.pkg.tar.zst".into(),
+            format!("{temp_dir}/bcachefs-tools-3:1.11.0-1.1-x86_64.pkg.tar.zst"),
+            format!("{temp_dir}/cachyos-cli-installer-new-0.7.0-3-x86_64.pkg.tar.zst"),
+            format!("{temp_dir}/dolt-1.30.4-1.1-x86_64.pkg.tar.zst"),
+            format!("{temp_dir}/dwl-git-0.2.1.r34.2d9740c-1-x86_64.pkg.tar.zst"),
+            format!("{temp_dir}/dwm-6.2-4-x86_64.pkg.tar.zst"),
+            format!("{temp_dir}/lightdm-webkit2-theme-arch-1:0.1-1-any.pkg.tar.zst"),
+            format!("{temp_dir}/plymouth-theme-hud-3-git-r38.bf2f570-1-any.pkg.tar.zst"),
+            format!("{temp_dir}/st-0.8.4-2-x86_64.pkg.tar.zst"),
         ];
+        create_test_pkgs(&pkgs_list);
         let new_pkgs_list = get_new_pkgs(&pkgs_list);
 
         let expected_new_pkgs_list: Vec<String> = vec![];
         assert_eq!(new_pkgs_list, expected_new_pkgs_list);
+
+        fs::remove_dir_all(temp_dir).unwrap();
     }
 
     #[test]
@@ -604,24 +918,32 @@ mod tests {
             "local_repo/x86_64/plymouth-theme-hud-3-git-r38.bf2f570-1-any.pkg.tar.zst".into(),
             "local_repo/x86_64/st-0.8.4-2-x86_64.pkg.tar.zst".into(),
         ];
-        assert!(validate_packages(false, &pkgs_list));
+        let profile = config::Profile { require_signature: false, ..Default::default() };
+        assert!(validate_packages(&profile, &pkgs_list));
     }
 
     #[test]
-    fn test_validate_packages_all_signatures_present() {
+    fn test_validate_packages_all_signatures_valid() {
         let temp_dir = utils::create_temporary_directory(None).expect("Failed to create temp dir");
+        let keyring_path = format!("{temp_dir}/trusted.gpg");
+        let (gnupg_home, _) = sig_tests::create_test_key(&temp_dir, "builder", &[]);
+        sig_tests::export_keyring(&gnupg_home, &keyring_path);
 
         let pkgs_list: Vec<String> = vec![
-            format!("{temp_dir}/lightdm-webkit2-theme-arch-1:0.1-1-any.pkg.tar.zst"),
-            format!("{temp_dir}/plymouth-theme-hud-3-git-r38.bf2f570-1-any.pkg.tar.zst"),
-            format!("{temp_dir}/st-0.8.4-2-x86_64.pkg.tar.zst"),
+            utils::create_test_package(&temp_dir, "lightdm-webkit2-theme-arch", "1:0.1-1", "any"),
+            utils::create_test_package(&temp_dir, "st", "0.8.4-2", "x86_64"),
         ];
         for pkg in &pkgs_list {
-            fs::File::create(pkg).unwrap();
-            fs::File::create(format!("{pkg}.sig")).unwrap();
+            sig_tests::sign_file(&gnupg_home, pkg, &[]);
         }
-        assert!(validate_packages(true, &pkgs_list));
-
+        let profile = config::Profile {
+            require_signature: true,
+            keyring: Some(keyring_path),
+            ..Default::default()
+        };
+        assert!(validate_packages(&profile, &pkgs_list));
+
+        sig_tests::stop_gpg_agent(&gnupg_home);
         fs::remove_dir_all(temp_dir).unwrap();
     }
 
@@ -637,7 +959,20 @@ mod tests {
         for pkg in &pkgs_list {
             fs::File::create(pkg).unwrap();
         }
-        assert!(!validate_packages(true, &pkgs_list));
+        let profile = config::Profile { require_signature: true, ..Default::default() };
+        assert!(!validate_packages(&profile, &pkgs_list));
+
+        fs::remove_dir_all(temp_dir).unwrap();
+    }
+
+    #[test]
+    fn test_validate_packages_empty_signature() {
+        let temp_dir = utils::create_temporary_directory(None).expect("Failed to create temp dir");
+
+        let pkg = utils::create_test_package(&temp_dir, "st", "0.8.4-2", "x86_64");
+        fs::File::create(format!("{pkg}.sig")).unwrap();
+        let profile = config::Profile { require_signature: true, ..Default::default() };
+        assert!(!validate_packages(&profile, &[pkg]));
 
         fs::remove_dir_all(temp_dir).unwrap();
     }
@@ -645,25 +980,81 @@ mod tests {
     #[test]
     fn test_validate_packages_empty_package_list() {
         let pkgs_list: Vec<String> = vec![];
-        assert!(validate_packages(true, &pkgs_list));
-        assert!(validate_packages(false, &pkgs_list));
+        let mut profile = config::Profile { require_signature: true, ..Default::default() };
+        assert!(validate_packages(&profile, &pkgs_list));
+        profile.require_signature = false;
+        assert!(validate_packages(&profile, &pkgs_list));
+    }
+
+    #[test]
+    fn test_validate_packages_allowed_signers_without_required_signature() {
+        let temp_dir = utils::create_temporary_directory(None).expect("Failed to create temp dir");
+        let keyring_path = format!("{temp_dir}/trusted.gpg");
+        let (gnupg_home, fingerprint) = sig_tests::create_test_key(&temp_dir, "builder", &[]);
+        let (other_gnupg_home, other_fingerprint) =
+            sig_tests::create_test_key(&temp_dir, "stranger", &[]);
+        sig_tests::export_keyring(&gnupg_home, &keyring_path);
+
+        let pkg = utils::create_test_package(&temp_dir, "st", "0.8.4-2", "x86_64");
+        let unsigned_pkg = utils::create_test_package(&temp_dir, "dwm", "6.2-4", "x86_64");
+        sig_tests::sign_file(&gnupg_home, &pkg, &[]);
+        let mut profile = config::Profile {
+            require_signature: false,
+            keyring: Some(keyring_path),
+            allowed_signers: vec![other_fingerprint],
+            ..Default::default()
+        };
+
+        // the allowed signers are enforced even if the signature isn't required
+        assert!(!validate_packages(&profile, std::slice::from_ref(&pkg)));
+        assert!(!validate_packages(&profile, std::slice::from_ref(&unsigned_pkg)));
+        let mut valid_pkgs_list = vec![pkg.clone(), unsigned_pkg.clone()];
+        remove_pkgs_without_valid_sig(&mut valid_pkgs_list, &profile);
+        assert!(valid_pkgs_list.is_empty());
+
+        profile.allowed_signers = vec![fingerprint];
+        assert!(validate_packages(&profile, std::slice::from_ref(&pkg)));
+        profile.allowed_signers = vec![];
+        assert!(validate_packages(&profile, &[pkg, unsigned_pkg]));
+
+        sig_tests::stop_gpg_agent(&gnupg_home);
+        sig_tests::stop_gpg_agent(&other_gnupg_home);
+        fs::remove_dir_all(temp_dir).unwrap();
     }
 
     #[test]
     fn test_validate_packages_mixed_signature_status() {
         let temp_dir = utils::create_temporary_directory(None).expect("Failed to create temp dir");
+        let keyring_path = format!("{temp_dir}/trusted.gpg");
+        let (gnupg_home, _) = sig_tests::create_test_key(&temp_dir, "builder", &[]);
+        let (other_gnupg_home, other_fingerprint) =
+            sig_tests::create_test_key(&temp_dir, "stranger", &[]);
+        sig_tests::export_keyring(&gnupg_home, &keyring_path);
 
         let pkgs_list: Vec<String> = vec![
-            format!("{temp_dir}/lightdm-webkit2-theme-arch-1:0.1-1-any.pkg.tar.zst"),
-            format!("{temp_dir}/plymouth-theme-hud-3-git-r38.bf2f570-1-any.pkg.tar.zst"),
-            format!("{temp_dir}/st-0.8.4-2-x86_64.pkg.tar.zst"),
+            utils::create_test_package(&temp_dir, "lightdm-webkit2-theme-arch", "1:0.1-1", "any"),
+            utils::create_test_package(&temp_dir, "st", "0.8.4-2", "x86_64"),
+            utils::create_test_package(&temp_dir, "dwm", "6.2-4", "x86_64"),
         ];
-        for pkg in &pkgs_list {
-            fs::File::create(pkg).unwrap();
-        }
-        fs::File::create(format!("{}.sig", &pkgs_list[0])).unwrap();
-        assert!(!validate_packages(true, &pkgs_list));
-
+        sig_tests::sign_file(&gnupg_home, &pkgs_list[0], &[]);
+        sig_tests::sign_file(&other_gnupg_home, &pkgs_list[1], &[]);
+        let mut profile = config::Profile {
+            require_signature: true,
+            keyring: Some(keyring_path),
+            ..Default::default()
+        };
+        assert!(!validate_packages(&profile, &pkgs_list));
+
+        let mut valid_pkgs_list = pkgs_list.clone();
+        remove_pkgs_without_valid_sig(&mut valid_pkgs_list, &profile);
+        assert_eq!(valid_pkgs_list, &pkgs_list[..1]);
+
+        // the package signed by the key from keyring is refused, if it's not allowed signer
+        profile.allowed_signers = vec![other_fingerprint];
+        assert!(!validate_packages(&profile, &pkgs_list[..1]));
+
+        sig_tests::stop_gpg_agent(&gnupg_home);
+        sig_tests::stop_gpg_agent(&other_gnupg_home);
         fs::remove_dir_all(temp_dir).unwrap();
     }
 
@@ -673,16 +1064,45 @@ mod tests {
         let other_file = Path::new(&temp_dir).join("other.txt");
         fs::File::create(other_file).unwrap();
 
-        let result = find_packages_in_dir(Path::new(&temp_dir)).unwrap();
+        let result = find_packages_in_dir(Path::new(&temp_dir), &all_pkg_extensions()).unwrap();
         assert_eq!(result.len(), 0);
 
         fs::remove_dir_all(temp_dir).unwrap();
     }
 
+    #[test]
+    fn test_find_packages_in_dir_all_extensions() {
+        let temp_dir = utils::create_temporary_directory(None).expect("Failed to create temp dir");
+
+        let pkgs_list: Vec<String> = vec![
+            format!("{temp_dir}/bcachefs-tools-3:1.11.0-1.1-x86_64.pkg.tar.gz"),
+            format!("{temp_dir}/dash-0.5.12-1.1-x86_64.pkg.tar.zst"),
+            format!("{temp_dir}/dolt-1.30.4-1.1-x86_64.pkg.tar.lz4"),
+            format!("{temp_dir}/dwm-6.2-4-x86_64.pkg.tar.bz2"),
+            format!("{temp_dir}/emacs-29.4-2.1-x86_64.pkg.tar"),
+            format!("{temp_dir}/st-0.8.4-2-x86_64.pkg.tar.xz"),
+        ];
+        for pkg in &pkgs_list {
+            fs::File::create(pkg).unwrap();
+            fs::File::create(format!("{pkg}.sig")).unwrap();
+        }
+        fs::File::create(format!("{temp_dir}/argon2-20190702-5.1-x86_64.pkg.tar.lrz")).unwrap();
+
+        let result = find_packages_in_dir(Path::new(&temp_dir), &all_pkg_extensions()).unwrap();
+        assert_eq!(result, pkgs_list);
+
+        // limit to the certain extensions
+        let pkg_extensions = vec![".pkg.tar.zst".to_owned(), ".pkg.tar.xz".to_owned()];
+        let result = find_packages_in_dir(Path::new(&temp_dir), &pkg_extensions).unwrap();
+        assert_eq!(result, vec![pkgs_list[1].clone(), pkgs_list[5].clone()]);
+
+        fs::remove_dir_all(temp_dir).unwrap();
+    }
+
     #[test]
     fn test_find_packages_in_dir_invalid_path() {
         let invalid_path = Path::new("/this/path/does/not/exist");
-        let result = find_packages_in_dir(invalid_path);
+        let result = find_packages_in_dir(invalid_path, &all_pkg_extensions());
         assert!(result.is_ok());
         assert_eq!(result.unwrap(), Vec::<String>::new());
     }
@@ -0,0 +1,213 @@
+use crate::compression;
+
+use std::fs;
+use std::io::Read;
+use std::path::Path;
+
+use anyhow::{Context, Result};
+
+/// Package metadata as stored in the `.PKGINFO` file of the package archive
+#[derive(Debug, PartialEq, Default, Clone)]
+pub struct PkgInfo {
+    pub name: String,
+    pub base: Option<String>,
+    pub version: String,
+    pub desc: Option<String>,
+    pub url: Option<String>,
+    pub builddate: Option<i64>,
+    pub packager: Option<String>,
+    pub size: Option<u64>,
+    pub arch: String,
+    pub licenses: Vec<String>,
+    pub groups: Vec<String>,
+    pub replaces: Vec<String>,
+    pub conflicts: Vec<String>,
+    pub provides: Vec<String>,
+    pub depends: Vec<String>,
+    pub optdepends: Vec<String>,
+    pub makedepends: Vec<String>,
+    pub checkdepends: Vec<String>,
+}
+
+// Reads the .PKGINFO from the provided package archive
+pub fn read_pkginfo(pkg_filepath: &str) -> Result<PkgInfo> {
+    let mut archive = open_pkg_archive(pkg_filepath)?;
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+        if entry.path()? != Path::new(".PKGINFO") {
+            continue;
+        }
+
+        let mut content = String::new();
+        entry.read_to_string(&mut content)?;
+        return parse_pkginfo(&content)
+            .with_context(|| format!("Invalid .PKGINFO in package '{pkg_filepath}'"));
+    }
+
+    anyhow::bail!("Package '{pkg_filepath}' doesn't contain .PKGINFO");
+}
+
+// Reads the list of files installed by the provided package, in the format of files DB.
+// Directories have trailing slash, package metadata files (e.g .PKGINFO) are skipped
+pub fn read_pkg_filelist(pkg_filepath: &str) -> Result<Vec<String>> {
+    let mut archive = open_pkg_archive(pkg_filepath)?;
+
+    let mut filelist: Vec<String> = vec![];
+    for entry in archive.entries()? {
+        let entry = entry?;
+        let entry_path = entry.path()?;
+        let entry_path =
+            entry_path.to_str().context("Invalid file path in package")?.trim_end_matches('/');
+        if entry_path.starts_with('.') {
+            continue;
+        }
+
+        if entry.header().entry_type().is_dir() {
+            filelist.push(format!("{entry_path}/"));
+        } else {
+            filelist.push(entry_path.to_owned());
+        }
+    }
+    filelist.sort();
+    filelist.dedup();
+
+    Ok(filelist)
+}
+
+// Parses the content of .PKGINFO
+pub fn parse_pkginfo(content: &str) -> Result<PkgInfo> {
+    let mut pkginfo = PkgInfo::default();
+
+    for line in content.lines() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let Some((key, value)) = line.split_once('=') else {
+            log::debug!("skipping malformed .PKGINFO line '{line}'");
+            continue;
+        };
+        let (key, value) = (key.trim(), value.trim().to_owned());
+
+        match key {
+            "pkgname" => pkginfo.name = value,
+            "pkgbase" => pkginfo.base = Some(value),
+            "pkgver" => pkginfo.version = value,
+            "pkgdesc" => pkginfo.desc = Some(value),
+            "url" => pkginfo.url = Some(value),
+            "builddate" => pkginfo.builddate = Some(value.parse()?),
+            "packager" => pkginfo.packager = Some(value),
+            "size" => pkginfo.size = Some(value.parse()?),
+            "arch" => pkginfo.arch = value,
+            "license" => pkginfo.licenses.push(value),
+            "group" => pkginfo.groups.push(value),
+            "replaces" => pkginfo.replaces.push(value),
+            "conflict" => pkginfo.conflicts.push(value),
+            "provides" => pkginfo.provides.push(value),
+            "depend" => pkginfo.depends.push(value),
+            "optdepend" => pkginfo.optdepends.push(value),
+            "makedepend" => pkginfo.makedepends.push(value),
+            "checkdepend" => pkginfo.checkdepends.push(value),
+            // other keys(e.g backup, xdata) are not needed for the repo DB
+            _ => (),
+        }
+    }
+
+    if pkginfo.name.is_empty() || pkginfo.version.is_empty() || pkginfo.arch.is_empty() {
+        anyhow::bail!("pkgname, pkgver and arch are required");
+    }
+
+    Ok(pkginfo)
+}
+
+fn open_pkg_archive(pkg_filepath: &str) -> Result<tar::Archive<Box<dyn Read>>> {
+    let pkg_file = fs::File::open(pkg_filepath)
+        .with_context(|| format!("Failed to open package file '{pkg_filepath}'"))?;
+    Ok(tar::Archive::new(compression::open_decoder(pkg_filepath, pkg_file)?))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::pkginfo::*;
+    use crate::utils;
+
+    #[test]
+    fn test_parse_pkginfo() {
+        let content = r#"
+# Generated by makepkg 6.1.0
+# using fakeroot version 1.36
+pkgname = dash
+pkgbase = dash
+xdata = pkgtype=pkg
+pkgver = 0.5.12-1.1
+pkgdesc = POSIX compliant shell that aims to be as small as possible
+url = http://gondor.apana.org.au/~herbert/dash/
+builddate = 1712345678
+packager = CachyOS <admin@cachyos.org>
+size = 154732
+arch = x86_64
+license = BSD-3-Clause
+provides = sh
+depend = glibc
+depend = libedit
+makedepend = git
+"#;
+        let pkginfo = parse_pkginfo(content).unwrap();
+        assert_eq!(pkginfo, PkgInfo {
+            name: "dash".to_owned(),
+            base: Some("dash".to_owned()),
+            version: "0.5.12-1.1".to_owned(),
+            desc: Some("POSIX compliant shell that aims to be as small as possible".to_owned()),
+            url: Some("http://gondor.apana.org.au/~herbert/dash/".to_owned()),
+            builddate: Some(1712345678),
+            packager: Some("CachyOS <admin@cachyos.org>".to_owned()),
+            size: Some(154732),
+            arch: "x86_64".to_owned(),
+            licenses: vec!["BSD-3-Clause".to_owned()],
+            provides: vec!["sh".to_owned()],
+            depends: vec!["glibc".to_owned(), "libedit".to_owned()],
+            makedepends: vec!["git".to_owned()],
+            ..Default::default()
+        });
+    }
+
+    #[test]
+    fn test_read_pkg_filelist() {
+        let temp_dir = utils::create_temporary_directory(None).expect("Failed to create temp dir");
+        let pkgfile = utils::create_test_package(&temp_dir, "dash", "0.5.12-1.1", "x86_64");
+
+        assert_eq!(read_pkginfo(&pkgfile).unwrap().name, "dash");
+        assert_eq!(read_pkg_filelist(&pkgfile).unwrap(), ["usr/share/doc/dash/README"]);
+
+        fs::remove_dir_all(temp_dir).unwrap();
+    }
+
+    #[test]
+    fn test_read_pkginfo_compressions() {
+        let temp_dir = utils::create_temporary_directory(None).expect("Failed to create temp dir");
+        let pkginfo_content = "pkgname = dash\npkgver = 0.5.12-1.1\narch = x86_64\n";
+
+        for pkg_ext in ["tar.xz", "tar.gz", "tar.bz2", "tar.lz4", "tar"] {
+            let pkgfile = format!("{temp_dir}/dash-0.5.12-1.1-x86_64.pkg.{pkg_ext}");
+            let pkg_file = fs::File::create(&pkgfile).unwrap();
+
+            let mut builder =
+                tar::Builder::new(compression::create_encoder(&pkgfile, pkg_file).unwrap());
+            let mut header = tar::Header::new_gnu();
+            header.set_mode(0o644);
+            header.set_size(pkginfo_content.len() as u64);
+            builder.append_data(&mut header, ".PKGINFO", pkginfo_content.as_bytes()).unwrap();
+            builder.into_inner().unwrap().finish().unwrap();
+
+            assert_eq!(read_pkginfo(&pkgfile).unwrap().version, "0.5.12-1.1", "'{pkg_ext}'");
+        }
+
+        fs::remove_dir_all(temp_dir).unwrap();
+    }
+
+    #[test]
+    fn test_parse_pkginfo_missing_fields() {
+        assert!(parse_pkginfo("pkgname = dash\npkgver = 0.5.12-1.1\n").is_err());
+        assert!(parse_pkginfo("").is_err());
+    }
+}
@@ -0,0 +1,77 @@
+use std::io::{BufRead, Write};
+
+use anyhow::Result;
+
+/// Answer to the confirmation prompt
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Answer {
+    /// Confirm the current item
+    Yes,
+    /// Skip the current item
+    No,
+    /// Confirm the current item and all the following ones
+    All,
+    /// Stop the command
+    Quit,
+}
+
+// Parses the user answer, an empty answer is treated as 'no' to be on the safe side
+pub fn parse_answer(input: &str) -> Option<Answer> {
+    let answer = match input.trim().to_lowercase().as_str() {
+        "y" | "yes" => Answer::Yes,
+        "n" | "no" | "" => Answer::No,
+        "a" | "all" => Answer::All,
+        "q" | "quit" => Answer::Quit,
+        _ => return None,
+    };
+    Some(answer)
+}
+
+// Asks the question until a valid answer is given. The end of input is treated as 'quit'
+pub fn ask(question: &str, input: &mut impl BufRead, output: &mut impl Write) -> Result<Answer> {
+    loop {
+        write!(output, "{question} [y]es/[N]o/[a]ll/[q]uit: ")?;
+        output.flush()?;
+
+        let mut line = String::new();
+        if input.read_line(&mut line)? == 0 {
+            writeln!(output)?;
+            return Ok(Answer::Quit);
+        }
+        if let Some(answer) = parse_answer(&line) {
+            return Ok(answer);
+        }
+        writeln!(output, "Unknown answer '{}'", line.trim())?;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::prompt::*;
+
+    use std::io::Cursor;
+
+    #[test]
+    fn test_parse_answer() {
+        assert_eq!(parse_answer("y\n"), Some(Answer::Yes));
+        assert_eq!(parse_answer("YES"), Some(Answer::Yes));
+        assert_eq!(parse_answer("n"), Some(Answer::No));
+        assert_eq!(parse_answer("\n"), Some(Answer::No));
+        assert_eq!(parse_answer("all"), Some(Answer::All));
+        assert_eq!(parse_answer(" q "), Some(Answer::Quit));
+        assert_eq!(parse_answer("maybe"), None);
+    }
+
+    #[test]
+    fn test_ask() {
+        let mut output = vec![];
+        let answer = ask("Remove?", &mut Cursor::new("what\na\n"), &mut output).unwrap();
+        assert_eq!(answer, Answer::All);
+        let output = String::from_utf8(output).unwrap();
+        assert_eq!(output.matches("Remove? [y]es/[N]o/[a]ll/[q]uit: ").count(), 2);
+        assert!(output.contains("Unknown answer 'what'"));
+
+        let answer = ask("Remove?", &mut Cursor::new(""), &mut vec![]).unwrap();
+        assert_eq!(answer, Answer::Quit);
+    }
+}
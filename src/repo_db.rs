@@ -0,0 +1,688 @@
+use crate::{compression, pkg_utils, pkginfo, sig_utils, status};
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::Read;
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use anyhow::{Context, Result};
+use base64::Engine;
+use md5::Md5;
+use sha2::{Digest, Sha256};
+use subprocess::{Exec, Redirection};
+
+/// Parameters of repo-add/repo-remove which are supported by the native DB writer
+#[derive(Debug, PartialEq, Default)]
+pub struct DbParams {
+    /// Sign the DB with GnuPG after update
+    pub sign: bool,
+    /// Key to use when signing the DB
+    pub key: Option<String>,
+    /// Put package signatures into the DB
+    pub include_sigs: bool,
+    /// Verify package signatures before adding them into the DB
+    pub verify: bool,
+    /// Keyring to verify package signatures against, the default keyring of gpgv if not set
+    pub keyring: Option<String>,
+    /// Fingerprints of the keys which are allowed to sign packages, any key if empty
+    pub allowed_signers: Vec<String>,
+    /// Only add packages which are not already in the DB
+    pub new_only: bool,
+    /// Don't add packages which are older than the ones in the DB
+    pub prevent_downgrade: bool,
+}
+
+impl DbParams {
+    pub fn from_args(args: &[String]) -> Result<Self> {
+        let mut params = Self::default();
+
+        let mut args_iter = args.iter();
+        while let Some(arg) = args_iter.next() {
+            match arg.as_str() {
+                "-s" | "--sign" => params.sign = true,
+                "--include-sigs" => params.include_sigs = true,
+                "-v" | "--verify" => params.verify = true,
+                "-n" | "--new" => params.new_only = true,
+                "-p" | "--prevent-downgrade" => params.prevent_downgrade = true,
+                "-k" | "--key" => {
+                    let key = args_iter.next().context("Missing value for '--key'")?;
+                    params.key = Some(key.clone());
+                },
+                // doesn't affect the resulting DB
+                "-q" | "--quiet" | "--nocolor" => (),
+                _ => anyhow::bail!("Unsupported parameter for native DB writer: '{arg}'"),
+            }
+        }
+
+        Ok(params)
+    }
+}
+
+// Entry of the repo DB, where files are stored under the '{pkgname}-{pkgver}' dir
+#[derive(Debug, PartialEq, Clone)]
+struct DbEntry {
+    dirname: String,
+    files: Vec<(String, Vec<u8>)>,
+}
+
+// Map of all entries in the repo DB. where:
+// (PKGNAME, DbEntry)
+type DbEntries = BTreeMap<String, DbEntry>;
+
+// Adds provided package files into the repo DB and the files DB,
+// replacing entries of the same packages
+pub fn add_packages(repo_db_path: &str, params: &DbParams, pkgfiles: &[String]) -> Result<()> {
+    let repo_files_path = pkg_utils::get_repo_files_path(repo_db_path);
+
+    let mut db_entries = read_db_entries(repo_db_path)?;
+    let mut files_entries = read_db_entries(&repo_files_path)?;
+
+    for pkgfile in pkgfiles {
+        if params.verify {
+            let sig_status = sig_utils::check_pkg_signer(
+                pkgfile,
+                params.keyring.as_deref(),
+                &params.allowed_signers,
+            );
+            if !sig_status.is_valid() {
+                anyhow::bail!(status::ValidationError(format!(
+                    "Failed to verify signature of package '{pkgfile}': {sig_status}"
+                )));
+            }
+        }
+
+        let pkginfo = pkginfo::read_pkginfo(pkgfile)?;
+
+        if let Some(existing_ver) = db_entries.get(&pkginfo.name).and_then(get_entry_version) {
+            if params.new_only {
+                log::warn!("Skipping '{}', it is already in the DB", pkginfo.name);
+                continue;
+            }
+            if params.prevent_downgrade
+                && alpm::vercmp(existing_ver.as_str(), pkginfo.version.as_str()).is_gt()
+            {
+                log::warn!(
+                    "Skipping '{}', newer version '{existing_ver}' is already in the DB",
+                    pkginfo.name
+                );
+                continue;
+            }
+            log::debug!("replacing '{}-{existing_ver}' in the DB", pkginfo.name);
+        }
+
+        let pkg_desc = build_pkg_desc(pkgfile, &pkginfo, params.include_sigs)?;
+        let pkg_files = build_pkg_files(pkgfile)?;
+        log::info!("Adding package '{}-{}' into the DB", pkginfo.name, pkginfo.version);
+
+        let dirname = format!("{}-{}", pkginfo.name, pkginfo.version);
+        let desc_file = ("desc".to_owned(), pkg_desc.into_bytes());
+        files_entries.insert(pkginfo.name.clone(), DbEntry {
+            dirname: dirname.clone(),
+            files: vec![desc_file.clone(), ("files".to_owned(), pkg_files.into_bytes())],
+        });
+        db_entries.insert(pkginfo.name, DbEntry { dirname, files: vec![desc_file] });
+    }
+
+    write_db_entries(repo_db_path, &db_entries)?;
+    finalize_db(repo_db_path, "db", params)?;
+    write_db_entries(&repo_files_path, &files_entries)?;
+    finalize_db(&repo_files_path, "files", params)
+}
+
+// Removes provided packages from the repo DB and the files DB.
+// Returns names of the packages which were not present in the DB
+pub fn remove_packages(
+    repo_db_path: &str,
+    rm_params: &[String],
+    pkgname_list: &[String],
+) -> Result<Vec<String>> {
+    let params = DbParams::from_args(rm_params)?;
+    if !Path::new(repo_db_path).exists() {
+        anyhow::bail!("repo DB '{repo_db_path}' doesn't exist");
+    }
+    let mut db_entries = read_db_entries(repo_db_path)?;
+
+    let mut missing_pkgs: Vec<String> = vec![];
+    for pkgname in pkgname_list {
+        match db_entries.remove(pkgname) {
+            Some(db_entry) => log::info!("Removing package '{}' from the DB", db_entry.dirname),
+            None => missing_pkgs.push(pkgname.clone()),
+        }
+    }
+
+    // nothing to rewrite
+    if missing_pkgs.len() == pkgname_list.len() {
+        return Ok(missing_pkgs);
+    }
+
+    write_db_entries(repo_db_path, &db_entries)?;
+    finalize_db(repo_db_path, "db", &params)?;
+
+    // the files DB may not exist, e.g if it was removed manually
+    let repo_files_path = pkg_utils::get_repo_files_path(repo_db_path);
+    if Path::new(&repo_files_path).exists() {
+        let mut files_entries = read_db_entries(&repo_files_path)?;
+        files_entries.retain(|pkgname, _| !pkgname_list.contains(pkgname));
+
+        write_db_entries(&repo_files_path, &files_entries)?;
+        finalize_db(&repo_files_path, "files", &params)?;
+    }
+
+    Ok(missing_pkgs)
+}
+
+/// Mismatch between the repo DB and the files DB
+#[derive(Debug, PartialEq)]
+pub enum FilesDbMismatch {
+    /// Package is in the repo DB, but not in the files DB
+    MissingInFilesDb(String),
+    /// Package is in the files DB, but not in the repo DB
+    MissingInDb(String),
+    /// Package has different versions in the DBs
+    VersionMismatch { pkgname: String, db_version: String, files_version: String },
+    /// Package entry of the files DB doesn't have the list of files
+    MissingFileList(String),
+}
+
+impl std::fmt::Display for FilesDbMismatch {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::MissingInFilesDb(pkgname) => write!(f, "'{pkgname}' is missing in the files DB"),
+            Self::MissingInDb(pkgname) => write!(f, "'{pkgname}' is missing in the repo DB"),
+            Self::VersionMismatch { pkgname, db_version, files_version } => write!(
+                f,
+                "'{pkgname}' has version '{db_version}' in the repo DB, but '{files_version}' in \
+                 the files DB"
+            ),
+            Self::MissingFileList(pkgname) => {
+                write!(f, "'{pkgname}' doesn't have the list of files in the files DB")
+            },
+        }
+    }
+}
+
+// Checks if the files DB has the same packages as the repo DB
+pub fn check_files_db(repo_db_path: &str) -> Result<Vec<FilesDbMismatch>> {
+    let repo_files_path = pkg_utils::get_repo_files_path(repo_db_path);
+    if !Path::new(&repo_files_path).exists() {
+        anyhow::bail!("files DB '{repo_files_path}' doesn't exist");
+    }
+
+    let db_entries = read_db_entries(repo_db_path)?;
+    let files_entries = read_db_entries(&repo_files_path)?;
+
+    let mut mismatches: Vec<FilesDbMismatch> = vec![];
+    for (pkgname, db_entry) in &db_entries {
+        let Some(files_entry) = files_entries.get(pkgname) else {
+            mismatches.push(FilesDbMismatch::MissingInFilesDb(pkgname.clone()));
+            continue;
+        };
+
+        let db_version = get_entry_version(db_entry).unwrap_or_default();
+        let files_version = get_entry_version(files_entry).unwrap_or_default();
+        if db_version != files_version {
+            mismatches.push(FilesDbMismatch::VersionMismatch {
+                pkgname: pkgname.clone(),
+                db_version,
+                files_version,
+            });
+        }
+        if !files_entry.files.iter().any(|(filename, _)| filename == "files") {
+            mismatches.push(FilesDbMismatch::MissingFileList(pkgname.clone()));
+        }
+    }
+    for pkgname in files_entries.keys().filter(|x| !db_entries.contains_key(*x)) {
+        mismatches.push(FilesDbMismatch::MissingInDb(pkgname.clone()));
+    }
+
+    Ok(mismatches)
+}
+
+fn read_db_entries(repo_db_path: &str) -> Result<DbEntries> {
+    let mut db_entries = DbEntries::new();
+    if !Path::new(repo_db_path).exists() {
+        log::debug!("repo DB '{repo_db_path}' doesn't exist yet, creating new one");
+        return Ok(db_entries);
+    }
+
+    let db_file = fs::File::open(repo_db_path)?;
+    let mut archive = tar::Archive::new(compression::open_decoder(repo_db_path, db_file)?);
+
+    // group the files by directory
+    let mut entry_dirs: BTreeMap<String, Vec<(String, Vec<u8>)>> = BTreeMap::new();
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+        if !entry.header().entry_type().is_file() {
+            continue;
+        }
+
+        let entry_path = entry.path()?.to_str().context("Invalid DB entry path")?.to_owned();
+        let Some((dirname, filename)) = entry_path.split_once('/') else {
+            log::warn!("Skipping unexpected DB entry '{entry_path}'");
+            continue;
+        };
+
+        let mut content = vec![];
+        entry.read_to_end(&mut content)?;
+        entry_dirs.entry(dirname.to_owned()).or_default().push((filename.to_owned(), content));
+    }
+
+    for (dirname, files) in entry_dirs {
+        let db_entry = DbEntry { dirname, files };
+        let pkg_name = get_entry_field(&db_entry, "NAME")
+            .with_context(|| format!("DB entry '{}' doesn't have name", db_entry.dirname))?;
+        db_entries.insert(pkg_name, db_entry);
+    }
+
+    Ok(db_entries)
+}
+
+fn write_db_entries(repo_db_path: &str, db_entries: &DbEntries) -> Result<()> {
+    let mtime = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
+
+    // write into temporary file first, so we don't end up with broken DB on failure
+    let tmp_db_path = format!("{repo_db_path}.part");
+    let tmp_db_file = fs::File::create(&tmp_db_path)
+        .with_context(|| format!("Failed to create DB file '{tmp_db_path}'"))?;
+
+    let mut builder = tar::Builder::new(compression::create_encoder(repo_db_path, tmp_db_file)?);
+    for db_entry in db_entries.values() {
+        let mut header = tar::Header::new_gnu();
+        header.set_entry_type(tar::EntryType::Directory);
+        header.set_mode(0o755);
+        header.set_mtime(mtime);
+        header.set_size(0);
+        builder.append_data(&mut header, &db_entry.dirname, std::io::empty())?;
+
+        for (filename, content) in &db_entry.files {
+            let mut header = tar::Header::new_gnu();
+            header.set_entry_type(tar::EntryType::Regular);
+            header.set_mode(0o644);
+            header.set_mtime(mtime);
+            header.set_size(content.len() as u64);
+            let entry_path = format!("{}/{filename}", db_entry.dirname);
+            builder.append_data(&mut header, entry_path, content.as_slice())?;
+        }
+    }
+    builder.into_inner()?.finish()?;
+
+    fs::rename(&tmp_db_path, repo_db_path)
+        .with_context(|| format!("Failed to replace DB file '{repo_db_path}'"))?;
+
+    Ok(())
+}
+
+// Signs the DB if requested and creates the symlinks as repo-add does,
+// where db_kind is either 'db' or 'files'
+fn finalize_db(repo_db_path: &str, db_kind: &str, params: &DbParams) -> Result<()> {
+    let sig_path = format!("{repo_db_path}.sig");
+    if params.sign {
+        sign_file(repo_db_path, params.key.as_deref())?;
+    } else if Path::new(&sig_path).exists() {
+        // the old signature is no longer valid
+        fs::remove_file(&sig_path)?;
+    }
+
+    let repo_dir = Path::new(repo_db_path).parent().unwrap();
+    let repo_db_filename = Path::new(repo_db_path).file_name().unwrap().to_str().unwrap();
+    let repo_db_prefix = pkg_utils::get_repo_db_prefix(repo_db_path);
+
+    let link_path = repo_dir.join(format!("{repo_db_prefix}.{db_kind}"));
+    update_symlink(&link_path, repo_db_filename)?;
+    if params.sign {
+        let sig_link_path = repo_dir.join(format!("{repo_db_prefix}.{db_kind}.sig"));
+        update_symlink(&sig_link_path, &format!("{repo_db_filename}.sig"))?;
+    }
+
+    Ok(())
+}
+
+fn update_symlink(link_path: &Path, target: &str) -> Result<()> {
+    if link_path.file_name().unwrap() == target {
+        return Ok(());
+    }
+    if link_path.symlink_metadata().is_ok() {
+        fs::remove_file(link_path)?;
+    }
+    std::os::unix::fs::symlink(target, link_path)?;
+
+    Ok(())
+}
+
+fn build_pkg_desc(pkgfile: &str, pkginfo: &pkginfo::PkgInfo, include_sig: bool) -> Result<String> {
+    let pkg_content = fs::read(pkgfile)?;
+    let pkg_filename = Path::new(pkgfile).file_name().unwrap().to_str().unwrap();
+
+    let pgpsig = if include_sig {
+        let sig_path = format!("{pkgfile}.sig");
+        let sig_content = fs::read(&sig_path)
+            .with_context(|| format!("Failed to read package signature '{sig_path}'"))?;
+        Some(base64::engine::general_purpose::STANDARD.encode(sig_content))
+    } else {
+        None
+    };
+
+    let mut desc = String::new();
+    append_desc_field(&mut desc, "FILENAME", [pkg_filename]);
+    append_desc_field(&mut desc, "NAME", [&pkginfo.name]);
+    append_desc_field(&mut desc, "BASE", &pkginfo.base);
+    append_desc_field(&mut desc, "VERSION", [&pkginfo.version]);
+    append_desc_field(&mut desc, "DESC", &pkginfo.desc);
+    append_desc_field(&mut desc, "GROUPS", &pkginfo.groups);
+    append_desc_field(&mut desc, "CSIZE", [pkg_content.len().to_string()]);
+    append_desc_field(&mut desc, "ISIZE", pkginfo.size.map(|x| x.to_string()));
+    append_desc_field(&mut desc, "MD5SUM", [format!("{:x}", Md5::digest(&pkg_content))]);
+    append_desc_field(&mut desc, "SHA256SUM", [format!("{:x}", Sha256::digest(&pkg_content))]);
+    append_desc_field(&mut desc, "PGPSIG", pgpsig);
+    append_desc_field(&mut desc, "URL", &pkginfo.url);
+    append_desc_field(&mut desc, "LICENSE", &pkginfo.licenses);
+    append_desc_field(&mut desc, "ARCH", [&pkginfo.arch]);
+    append_desc_field(&mut desc, "BUILDDATE", pkginfo.builddate.map(|x| x.to_string()));
+    append_desc_field(&mut desc, "PACKAGER", &pkginfo.packager);
+    append_desc_field(&mut desc, "REPLACES", &pkginfo.replaces);
+    append_desc_field(&mut desc, "CONFLICTS", &pkginfo.conflicts);
+    append_desc_field(&mut desc, "PROVIDES", &pkginfo.provides);
+    append_desc_field(&mut desc, "DEPENDS", &pkginfo.depends);
+    append_desc_field(&mut desc, "OPTDEPENDS", &pkginfo.optdepends);
+    append_desc_field(&mut desc, "MAKEDEPENDS", &pkginfo.makedepends);
+    append_desc_field(&mut desc, "CHECKDEPENDS", &pkginfo.checkdepends);
+
+    Ok(desc)
+}
+
+fn build_pkg_files(pkgfile: &str) -> Result<String> {
+    let pkg_filelist = pkginfo::read_pkg_filelist(pkgfile)?;
+
+    let mut files = String::new();
+    append_desc_field(&mut files, "FILES", pkg_filelist);
+    Ok(files)
+}
+
+// Appends the field in the format of desc file, skips the field if it doesn't have values
+fn append_desc_field<T: AsRef<str>>(
+    desc: &mut String,
+    field: &str,
+    values: impl IntoIterator<Item = T>,
+) {
+    let mut values = values.into_iter().peekable();
+    if values.peek().is_none() {
+        return;
+    }
+
+    desc.push_str(&format!("%{field}%\n"));
+    for value in values {
+        desc.push_str(value.as_ref());
+        desc.push('\n');
+    }
+    desc.push('\n');
+}
+
+// Gets the first value of the field from the desc file of DB entry
+fn get_entry_field(db_entry: &DbEntry, field: &str) -> Option<String> {
+    let (_, desc_content) = db_entry.files.iter().find(|(filename, _)| filename == "desc")?;
+    let desc_content = String::from_utf8_lossy(desc_content);
+
+    let field_header = format!("%{field}%");
+    let mut desc_lines = desc_content.lines();
+    desc_lines.find(|line| *line == field_header)?;
+    desc_lines.next().map(str::to_owned)
+}
+
+fn get_entry_version(db_entry: &DbEntry) -> Option<String> {
+    get_entry_field(db_entry, "VERSION")
+}
+
+fn sign_file(filepath: &str, key: Option<&str>) -> Result<()> {
+    let mut gpg_args = vec!["--batch", "--yes", "--detach-sign", "--use-agent", "--no-armor"];
+    if let Some(key) = key {
+        gpg_args.extend(["-u", key]);
+    }
+    gpg_args.push(filepath);
+
+    let output = Exec::cmd("gpg")
+        .args(&gpg_args)
+        .stderr(Redirection::Merge)
+        .stdout(Redirection::Pipe)
+        .capture()?;
+
+    let proc_output = String::from_utf8_lossy(&output.stdout);
+    if !output.success() {
+        log::error!("gpg output:\n{proc_output}");
+        anyhow::bail!("Failed to sign '{filepath}'");
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::repo_db::*;
+    use crate::utils;
+
+    #[test]
+    fn test_db_params() {
+        let args = ["--sign", "--include-sigs", "--verify"].map(String::from);
+        assert_eq!(DbParams::from_args(&args).unwrap(), DbParams {
+            sign: true,
+            include_sigs: true,
+            verify: true,
+            ..Default::default()
+        });
+
+        let args = ["-k", "ABCD", "-p"].map(String::from);
+        assert_eq!(DbParams::from_args(&args).unwrap(), DbParams {
+            key: Some("ABCD".to_owned()),
+            prevent_downgrade: true,
+            ..Default::default()
+        });
+
+        assert!(DbParams::from_args(&["--unknown".to_owned()]).is_err());
+        assert!(DbParams::from_args(&["--key".to_owned()]).is_err());
+    }
+
+    #[test]
+    fn test_add_packages() {
+        let temp_dir = utils::create_temporary_directory(None).expect("Failed to create temp dir");
+        let repo_db_path = format!("{temp_dir}/testrepo.db.tar.zst");
+
+        let pkgfiles = vec![
+            utils::create_test_package(&temp_dir, "dash", "0.5.12-1.1", "x86_64"),
+            utils::create_test_package(&temp_dir, "st", "0.8.4-2", "x86_64"),
+        ];
+        add_packages(&repo_db_path, &DbParams::default(), &pkgfiles).unwrap();
+
+        let db_entries = read_db_entries(&repo_db_path).unwrap();
+        assert_eq!(db_entries.keys().collect::<Vec<_>>(), ["dash", "st"]);
+        assert_eq!(db_entries["dash"].dirname, "dash-0.5.12-1.1");
+        assert_eq!(
+            get_entry_field(&db_entries["dash"], "FILENAME").unwrap(),
+            Path::new(&pkgfiles[0]).file_name().unwrap().to_str().unwrap()
+        );
+        assert_eq!(get_entry_field(&db_entries["dash"], "ARCH").unwrap(), "x86_64");
+        assert_eq!(
+            get_entry_field(&db_entries["st"], "SHA256SUM").unwrap(),
+            format!("{:x}", Sha256::digest(fs::read(&pkgfiles[1]).unwrap()))
+        );
+        assert!(get_entry_field(&db_entries["st"], "PGPSIG").is_none());
+        assert_eq!(
+            fs::read_link(format!("{temp_dir}/testrepo.db")).unwrap(),
+            Path::new("testrepo.db.tar.zst")
+        );
+
+        // newer version replaces the old entry
+        let newer_pkgfile = utils::create_test_package(&temp_dir, "dash", "0.5.12-2", "x86_64");
+        add_packages(&repo_db_path, &DbParams::default(), &[newer_pkgfile]).unwrap();
+
+        let db_entries = read_db_entries(&repo_db_path).unwrap();
+        assert_eq!(db_entries.len(), 2);
+        assert_eq!(db_entries["dash"].dirname, "dash-0.5.12-2");
+
+        // downgrade is prevented if requested
+        let params = DbParams::from_args(&["--prevent-downgrade".to_owned()]).unwrap();
+        add_packages(&repo_db_path, &params, &pkgfiles[..1]).unwrap();
+        let db_entries = read_db_entries(&repo_db_path).unwrap();
+        assert_eq!(get_entry_version(&db_entries["dash"]).unwrap(), "0.5.12-2");
+
+        fs::remove_dir_all(temp_dir).unwrap();
+    }
+
+    #[test]
+    fn test_add_packages_include_sigs() {
+        let temp_dir = utils::create_temporary_directory(None).expect("Failed to create temp dir");
+        let repo_db_path = format!("{temp_dir}/testrepo.db.tar.zst");
+
+        let pkgfile = utils::create_test_package(&temp_dir, "dash", "0.5.12-1.1", "x86_64");
+        fs::write(format!("{pkgfile}.sig"), b"signature").unwrap();
+        let params = DbParams { include_sigs: true, ..Default::default() };
+        add_packages(&repo_db_path, &params, &[pkgfile]).unwrap();
+
+        let db_entries = read_db_entries(&repo_db_path).unwrap();
+        assert_eq!(get_entry_field(&db_entries["dash"], "PGPSIG").unwrap(), "c2lnbmF0dXJl");
+
+        fs::remove_dir_all(temp_dir).unwrap();
+    }
+
+    #[test]
+    fn test_add_packages_verify() {
+        let temp_dir = utils::create_temporary_directory(None).expect("Failed to create temp dir");
+        let repo_db_path = format!("{temp_dir}/testrepo.db.tar.zst");
+
+        // the package is rejected the same way as on validation, the DB isn't created
+        let pkgfile = utils::create_test_package(&temp_dir, "dash", "0.5.12-1.1", "x86_64");
+        let params = DbParams { verify: true, ..Default::default() };
+        let add_err = add_packages(&repo_db_path, &params, &[pkgfile]).unwrap_err();
+        assert!(add_err.to_string().contains("signature file is missing"), "{add_err}");
+        assert_eq!(status::ExitStatus::from_error(&add_err), status::ExitStatus::ValidationFailed);
+        assert!(!Path::new(&repo_db_path).exists());
+
+        fs::remove_dir_all(temp_dir).unwrap();
+    }
+
+    #[test]
+    fn test_remove_packages() {
+        let temp_dir = utils::create_temporary_directory(None).expect("Failed to create temp dir");
+        let repo_db_path = format!("{temp_dir}/testrepo.db.tar.zst");
+
+        let pkgfiles = vec![
+            utils::create_test_package(&temp_dir, "dash", "0.5.12-1.1", "x86_64"),
+            utils::create_test_package(&temp_dir, "dwm", "6.2-4", "x86_64"),
+            utils::create_test_package(&temp_dir, "st", "0.8.4-2", "x86_64"),
+        ];
+        add_packages(&repo_db_path, &DbParams::default(), &pkgfiles).unwrap();
+
+        let pkgname_list = ["dwm", "st", "emacs"].map(String::from);
+        let missing_pkgs = remove_packages(&repo_db_path, &[], &pkgname_list).unwrap();
+        assert_eq!(missing_pkgs, ["emacs"]);
+
+        let db_entries = read_db_entries(&repo_db_path).unwrap();
+        assert_eq!(db_entries.keys().collect::<Vec<_>>(), ["dash"]);
+
+        // removing only unknown packages doesn't touch the DB
+        let missing_pkgs = remove_packages(&repo_db_path, &[], &["emacs".to_owned()]).unwrap();
+        assert_eq!(missing_pkgs, ["emacs"]);
+        assert_eq!(read_db_entries(&repo_db_path).unwrap(), db_entries);
+
+        fs::remove_dir_all(temp_dir).unwrap();
+    }
+
+    #[test]
+    fn test_remove_packages_missing_db() {
+        let temp_dir = utils::create_temporary_directory(None).expect("Failed to create temp dir");
+        let repo_db_path = format!("{temp_dir}/testrepo.db.tar.zst");
+
+        assert!(remove_packages(&repo_db_path, &[], &["dash".to_owned()]).is_err());
+
+        fs::remove_dir_all(temp_dir).unwrap();
+    }
+
+    #[test]
+    fn test_files_db() {
+        let temp_dir = utils::create_temporary_directory(None).expect("Failed to create temp dir");
+        let repo_db_path = format!("{temp_dir}/testrepo.db.tar.zst");
+        let repo_files_path = format!("{temp_dir}/testrepo.files.tar.zst");
+
+        let pkgfiles = vec![
+            utils::create_test_package(&temp_dir, "dash", "0.5.12-1.1", "x86_64"),
+            utils::create_test_package(&temp_dir, "st", "0.8.4-2", "x86_64"),
+        ];
+        add_packages(&repo_db_path, &DbParams::default(), &pkgfiles).unwrap();
+
+        let files_entries = read_db_entries(&repo_files_path).unwrap();
+        assert_eq!(files_entries.keys().collect::<Vec<_>>(), ["dash", "st"]);
+        assert_eq!(
+            files_entries["dash"].files[1],
+            ("files".to_owned(), b"%FILES%\nusr/share/doc/dash/README\n\n".to_vec())
+        );
+        assert_eq!(
+            fs::read_link(format!("{temp_dir}/testrepo.files")).unwrap(),
+            Path::new("testrepo.files.tar.zst")
+        );
+        assert_eq!(check_files_db(&repo_db_path).unwrap(), vec![]);
+
+        remove_packages(&repo_db_path, &[], &["st".to_owned()]).unwrap();
+        let files_entries = read_db_entries(&repo_files_path).unwrap();
+        assert_eq!(files_entries.keys().collect::<Vec<_>>(), ["dash"]);
+        assert_eq!(check_files_db(&repo_db_path).unwrap(), vec![]);
+
+        fs::remove_dir_all(temp_dir).unwrap();
+    }
+
+    #[test]
+    fn test_check_files_db_mismatch() {
+        let temp_dir = utils::create_temporary_directory(None).expect("Failed to create temp dir");
+        let repo_db_path = format!("{temp_dir}/testrepo.db.tar.zst");
+        let repo_files_path = format!("{temp_dir}/testrepo.files.tar.zst");
+
+        let pkgfiles = vec![
+            utils::create_test_package(&temp_dir, "dash", "0.5.12-1.1", "x86_64"),
+            utils::create_test_package(&temp_dir, "st", "0.8.4-2", "x86_64"),
+        ];
+        add_packages(&repo_db_path, &DbParams::default(), &pkgfiles).unwrap();
+
+        // modify only the repo DB
+        let mut db_entries = read_db_entries(&repo_db_path).unwrap();
+        db_entries.remove("st");
+        let emacs_pkgfile = utils::create_test_package(&temp_dir, "emacs", "29.4-2.1", "x86_64");
+        let emacs_pkginfo = pkginfo::read_pkginfo(&emacs_pkgfile).unwrap();
+        let emacs_desc = build_pkg_desc(&emacs_pkgfile, &emacs_pkginfo, false).unwrap();
+        db_entries.insert("emacs".to_owned(), DbEntry {
+            dirname: "emacs-29.4-2.1".to_owned(),
+            files: vec![("desc".to_owned(), emacs_desc.into_bytes())],
+        });
+        write_db_entries(&repo_db_path, &db_entries).unwrap();
+
+        // drop the list of files and change version of the package in the files DB
+        let mut files_entries = read_db_entries(&repo_files_path).unwrap();
+        let dash_entry = files_entries.get_mut("dash").unwrap();
+        dash_entry.files.retain(|(filename, _)| filename != "files");
+        let dash_desc = String::from_utf8(dash_entry.files[0].1.clone()).unwrap();
+        dash_entry.files[0].1 = dash_desc.replace("0.5.12-1.1", "0.5.12-2").into_bytes();
+        write_db_entries(&repo_files_path, &files_entries).unwrap();
+
+        assert_eq!(check_files_db(&repo_db_path).unwrap(), vec![
+            FilesDbMismatch::VersionMismatch {
+                pkgname: "dash".to_owned(),
+                db_version: "0.5.12-1.1".to_owned(),
+                files_version: "0.5.12-2".to_owned(),
+            },
+            FilesDbMismatch::MissingFileList("dash".to_owned()),
+            FilesDbMismatch::MissingInFilesDb("emacs".to_owned()),
+            FilesDbMismatch::MissingInDb("st".to_owned()),
+        ]);
+
+        fs::remove_file(&repo_files_path).unwrap();
+        assert!(check_files_db(&repo_db_path).is_err());
+
+        fs::remove_dir_all(temp_dir).unwrap();
+    }
+
+    #[test]
+    fn test_build_desc_format() {
+        let mut desc = String::new();
+        append_desc_field(&mut desc, "NAME", ["dash"]);
+        append_desc_field(&mut desc, "DEPENDS", ["glibc", "libedit"]);
+        append_desc_field(&mut desc, "GROUPS", Vec::<String>::new());
+        assert_eq!(desc, "%NAME%\ndash\n\n%DEPENDS%\nglibc\nlibedit\n\n");
+    }
+}
@@ -1,10 +1,33 @@
-use crate::config;
+use crate::context::Context;
+use crate::{config, repo_db};
 
 use anyhow::Result;
 use subprocess::{Exec, Redirection};
 
 // Calls repo-add on provided repo and package files
-pub fn handle_repo_add(profile: &config::Profile, pkgfiles: &[String]) -> Result<()> {
+pub fn handle_repo_add(
+    ctx: &Context,
+    profile: &config::Profile,
+    pkgfiles: &[String],
+) -> Result<()> {
+    if ctx.dry_run {
+        for pkgfile in pkgfiles {
+            println!("[dry-run] add to DB '{}': '{pkgfile}'", profile.repo);
+        }
+        return Ok(());
+    }
+    ctx.save_repo_db(&profile.repo)?;
+
+    if profile.db_backend == config::DbBackend::Native {
+        // the package signatures are verified the same way as on validation
+        let db_params = repo_db::DbParams {
+            keyring: profile.keyring.clone(),
+            allowed_signers: profile.allowed_signers.clone(),
+            ..repo_db::DbParams::from_args(&profile.add_params)?
+        };
+        return repo_db::add_packages(&profile.repo, &db_params, pkgfiles);
+    }
+
     let mut repo_add_args = profile.add_params.clone();
     repo_add_args.push(profile.repo.clone());
 
@@ -29,7 +52,28 @@ pub fn handle_repo_add(profile: &config::Profile, pkgfiles: &[String]) -> Result
 }
 
 // Calls repo-remove on provided repo and package names
-pub fn handle_repo_remove(profile: &config::Profile, pkgname_list: &[String]) -> Result<()> {
+pub fn handle_repo_remove(
+    ctx: &Context,
+    profile: &config::Profile,
+    pkgname_list: &[String],
+) -> Result<()> {
+    if ctx.dry_run {
+        for pkgname in pkgname_list {
+            println!("[dry-run] remove from DB '{}': '{pkgname}'", profile.repo);
+        }
+        return Ok(());
+    }
+    ctx.save_repo_db(&profile.repo)?;
+
+    if profile.db_backend == config::DbBackend::Native {
+        let missing_pkgs =
+            repo_db::remove_packages(&profile.repo, &profile.rm_params, pkgname_list)?;
+        if !missing_pkgs.is_empty() {
+            log::warn!("Packages not present in the DB '{}': {missing_pkgs:?}", profile.repo);
+        }
+        return Ok(());
+    }
+
     let mut repo_remove_args = profile.rm_params.clone();
     repo_remove_args.push(profile.repo.clone());
 
@@ -0,0 +1,193 @@
+use crate::pkg_utils;
+
+use std::path::Path;
+
+use anyhow::{Context, Result};
+use clap::ValueEnum;
+use serde::Serialize;
+
+/// Format of the command report
+#[derive(Debug, PartialEq, Default, Clone, Copy, ValueEnum)]
+pub enum OutputFormat {
+    /// Line per found package, e.g 'outdated dash 0.5.11-1 repof'
+    #[default]
+    Text,
+    Json,
+    Yaml,
+}
+
+/// Package in the report
+#[derive(Debug, PartialEq, Clone, Serialize)]
+pub struct PkgEntry {
+    pub name: String,
+    pub version: String,
+    pub filename: String,
+    /// Repo where the package is found
+    pub repo: String,
+}
+
+impl PkgEntry {
+    // Creates the entry from the metadata of the package file,
+    // the filename is only cross-checked against it
+    pub fn from_pkgfile(pkg_filepath: &str, repo: &str) -> Result<Self> {
+        let pkginfo = pkg_utils::read_pkg_metadata(pkg_filepath)
+            .with_context(|| format!("Failed to read metadata of package '{pkg_filepath}'"))?;
+        Ok(Self::from_db_record(&pkginfo.name, &pkginfo.version, pkg_filepath, repo))
+    }
+
+    // Creates the entry from the package record in the repo DB,
+    // the package file path doesn't need to exist
+    pub fn from_db_record(name: &str, version: &str, pkg_filepath: &str, repo: &str) -> Self {
+        let filename = Path::new(pkg_filepath).file_name().unwrap().to_str().unwrap();
+        Self {
+            name: name.to_owned(),
+            version: version.to_owned(),
+            filename: filename.to_owned(),
+            repo: repo.to_owned(),
+        }
+    }
+}
+
+/// Report of the repo checkup
+#[derive(Debug, PartialEq, Default, Serialize)]
+pub struct CheckupReport {
+    pub repo: String,
+    /// Packages which are not present in the DB
+    pub brand_new: Vec<PkgEntry>,
+    /// Newer versions of the packages, which are present in the DB
+    pub new: Vec<PkgEntry>,
+    /// Older versions of the packages in the repo dir
+    pub outdated: Vec<PkgEntry>,
+    /// Packages in the DB, which files don't exist
+    pub stale: Vec<PkgEntry>,
+    /// Newer versions of the packages in the reference repos
+    pub newer_in_reference: Vec<PkgEntry>,
+}
+
+impl CheckupReport {
+    // Checks if nothing is found, which needs an update of the repo
+    pub fn is_up_to_date(&self) -> bool {
+        self.brand_new.is_empty()
+            && self.new.is_empty()
+            && self.outdated.is_empty()
+            && self.stale.is_empty()
+            && self.newer_in_reference.is_empty()
+    }
+
+    // Serializes the report into the output format, each format ends with the newline
+    pub fn render(&self, output_format: OutputFormat) -> Result<String> {
+        let report = match output_format {
+            OutputFormat::Json => serde_json::to_string_pretty(self)? + "\n",
+            OutputFormat::Yaml => serde_yaml::to_string(self)?,
+            OutputFormat::Text => self.render_text(),
+        };
+        Ok(report)
+    }
+
+    // Renders the line per package with its category, which is easy to grep/awk
+    fn render_text(&self) -> String {
+        let mut report = String::new();
+        for (category, pkg_entries) in [
+            ("brand-new", &self.brand_new),
+            ("new", &self.new),
+            ("outdated", &self.outdated),
+            ("stale", &self.stale),
+            ("newer-in-reference", &self.newer_in_reference),
+        ] {
+            for pkg_entry in pkg_entries {
+                report += &format!(
+                    "{category} {} {} {}\n",
+                    pkg_entry.name, pkg_entry.version, pkg_entry.repo
+                );
+            }
+        }
+        report
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::report::*;
+    use crate::utils;
+
+    use std::fs;
+
+    #[test]
+    fn test_pkg_entry_from_pkgfile() {
+        let temp_dir = utils::create_temporary_directory(None).unwrap();
+        let pkgfile = utils::create_test_package(&temp_dir, "dash", "0.5.12-1.1", "x86_64");
+        let dash_entry = PkgEntry {
+            name: "dash".to_owned(),
+            version: "0.5.12-1.1".to_owned(),
+            filename: "dash-0.5.12-1.1-x86_64.pkg.tar.zst".to_owned(),
+            repo: "repof".to_owned(),
+        };
+        assert_eq!(PkgEntry::from_pkgfile(&pkgfile, "repof").unwrap(), dash_entry);
+
+        // the name and version are taken from the metadata, not from the unusual filename
+        let renamed_pkgfile = format!("{temp_dir}/dash-x86_64.pkg.tar.zst");
+        fs::rename(&pkgfile, &renamed_pkgfile).unwrap();
+        assert_eq!(PkgEntry::from_pkgfile(&renamed_pkgfile, "repof").unwrap(), PkgEntry {
+            filename: "dash-x86_64.pkg.tar.zst".to_owned(),
+            ..dash_entry.clone()
+        });
+
+        // the package without metadata isn't reported with the empty name
+        let broken_pkgfile = format!("{temp_dir}/st-0.8.4-2-x86_64.pkg.tar.zst");
+        fs::write(&broken_pkgfile, "").unwrap();
+        assert!(PkgEntry::from_pkgfile(&broken_pkgfile, "repof").is_err());
+
+        assert_eq!(
+            PkgEntry::from_db_record(
+                "dash",
+                "0.5.12-1.1",
+                "https://mirror.example.org/repof/dash-0.5.12-1.1-x86_64.pkg.tar.zst",
+                "repof"
+            ),
+            dash_entry
+        );
+
+        fs::remove_dir_all(temp_dir).unwrap();
+    }
+
+    #[test]
+    fn test_checkup_report() {
+        let report = CheckupReport {
+            repo: "repof".to_owned(),
+            outdated: vec![PkgEntry::from_db_record(
+                "dash",
+                "0.5.11-1",
+                "dash-0.5.11-1-x86_64.pkg.tar.zst",
+                "repof",
+            )],
+            ..Default::default()
+        };
+
+        let json_report: serde_json::Value =
+            serde_json::from_str(&report.render(OutputFormat::Json).unwrap()).unwrap();
+        assert_eq!(
+            json_report,
+            serde_json::json!({
+                "repo": "repof",
+                "brand_new": [],
+                "new": [],
+                "outdated": [{
+                    "name": "dash",
+                    "version": "0.5.11-1",
+                    "filename": "dash-0.5.11-1-x86_64.pkg.tar.zst",
+                    "repo": "repof",
+                }],
+                "stale": [],
+                "newer_in_reference": [],
+            })
+        );
+
+        let yaml_report = report.render(OutputFormat::Yaml).unwrap();
+        assert!(yaml_report.contains("outdated:\n- name: dash\n  version: 0.5.11-1\n"));
+        assert_eq!(report.render(OutputFormat::Text).unwrap(), "outdated dash 0.5.11-1 repof\n");
+        assert_eq!(CheckupReport::default().render(OutputFormat::Text).unwrap(), "");
+
+        assert!(!report.is_up_to_date());
+        assert!(CheckupReport::default().is_up_to_date());
+    }
+}
@@ -0,0 +1,386 @@
+use std::fmt;
+use std::path::Path;
+
+use subprocess::{Exec, Redirection};
+
+/// Result of the detached package signature verification
+#[derive(Debug, PartialEq, Clone)]
+pub enum SigStatus {
+    /// Signature is valid and made by the key with the fingerprint
+    Valid { fingerprint: String },
+    /// Signature file doesn't exist
+    Missing,
+    /// Signature doesn't match the package, e.g the package or the signature was modified
+    Bad { keyid: String },
+    /// Key which made the signature is not in the keyring
+    UnknownKey { keyid: String },
+    /// Key which made the signature has expired
+    ExpiredKey { keyid: String },
+    /// Key which made the signature was revoked
+    RevokedKey { keyid: String },
+    /// Signature itself has expired
+    ExpiredSig { keyid: String },
+    /// Signature file doesn't contain valid OpenPGP data or gpgv failed
+    Invalid { reason: String },
+    /// Signature is valid, but the key is not in the allowed signers
+    UntrustedSigner { fingerprint: String },
+}
+
+impl SigStatus {
+    pub fn is_valid(&self) -> bool {
+        matches!(self, Self::Valid { .. })
+    }
+}
+
+impl fmt::Display for SigStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Valid { fingerprint } => write!(f, "valid signature by key {fingerprint}"),
+            Self::Missing => write!(f, "signature file is missing"),
+            Self::Bad { keyid } => write!(f, "bad signature made by key {keyid}"),
+            Self::UnknownKey { keyid } => {
+                write!(f, "signature made by unknown key {keyid}, which is not in the keyring")
+            },
+            Self::ExpiredKey { keyid } => write!(f, "signature made by expired key {keyid}"),
+            Self::RevokedKey { keyid } => write!(f, "signature made by revoked key {keyid}"),
+            Self::ExpiredSig { keyid } => write!(f, "expired signature made by key {keyid}"),
+            Self::Invalid { reason } => write!(f, "invalid signature: {reason}"),
+            Self::UntrustedSigner { fingerprint } => {
+                write!(f, "signed by key {fingerprint}, which is not in the allowed signers")
+            },
+        }
+    }
+}
+
+// Verifies the detached signature '{pkg}.sig' of the package with gpgv against the keyring.
+// If the keyring is not provided, then the default keyring of gpgv is used
+pub fn verify_pkg_signature(pkg_filepath: &str, keyring: Option<&str>) -> SigStatus {
+    let sig_filepath = format!("{pkg_filepath}.sig");
+    if !Path::new(&sig_filepath).exists() {
+        return SigStatus::Missing;
+    }
+
+    let mut gpgv_args = vec!["--status-fd", "1"];
+    if let Some(keyring) = keyring {
+        gpgv_args.extend(["--keyring", keyring]);
+    }
+    gpgv_args.extend([sig_filepath.as_str(), pkg_filepath]);
+    log::debug!("gpgv_args := {gpgv_args:?}");
+
+    let output = match Exec::cmd("gpgv")
+        .args(&gpgv_args)
+        .stdout(Redirection::Pipe)
+        .stderr(Redirection::Pipe)
+        .capture()
+    {
+        Ok(output) => output,
+        Err(exec_err) => {
+            return SigStatus::Invalid { reason: format!("failed to run gpgv: {exec_err}") }
+        },
+    };
+    log::debug!("gpgv output:\n{}", String::from_utf8_lossy(&output.stderr));
+
+    let sig_status = parse_gpgv_status(&String::from_utf8_lossy(&output.stdout));
+    if sig_status.is_valid() && !output.success() {
+        return SigStatus::Invalid { reason: "gpgv reported failure".to_owned() };
+    }
+    sig_status
+}
+
+// Verifies the package signature and checks that it's made by one of the allowed signers.
+// Any valid signer is accepted, if the allowed signers are empty
+pub fn check_pkg_signer(
+    pkg_filepath: &str,
+    keyring: Option<&str>,
+    allowed_signers: &[String],
+) -> SigStatus {
+    let sig_status = verify_pkg_signature(pkg_filepath, keyring);
+    let SigStatus::Valid { fingerprint } = &sig_status else {
+        return sig_status;
+    };
+
+    if allowed_signers.is_empty()
+        || allowed_signers.iter().any(|x| normalize_fingerprint(x).as_ref() == Some(fingerprint))
+    {
+        return sig_status;
+    }
+    SigStatus::UntrustedSigner { fingerprint: fingerprint.clone() }
+}
+
+// Normalizes the key fingerprint to the form reported by gpg, e.g
+// '0x3B94 A80E 50A4 77C7' -> '3B94A80E50A477C7'. Returns None if it's not a full fingerprint
+pub fn normalize_fingerprint(fingerprint: &str) -> Option<String> {
+    let fingerprint = fingerprint.trim();
+    let fingerprint = fingerprint.strip_prefix("0x").unwrap_or(fingerprint);
+    let fingerprint = fingerprint.replace(' ', "").to_uppercase();
+
+    // v4 keys have 40 hex digits long fingerprint, v5/v6 keys 64
+    let is_full_fingerprint =
+        matches!(fingerprint.len(), 40 | 64) && fingerprint.chars().all(|x| x.is_ascii_hexdigit());
+    is_full_fingerprint.then_some(fingerprint)
+}
+
+// Parses the machine-readable status output of gpgv (--status-fd)
+fn parse_gpgv_status(status_output: &str) -> SigStatus {
+    let mut valid_fingerprint: Option<String> = None;
+    let mut failure: Option<SigStatus> = None;
+
+    for line in status_output.lines() {
+        let Some(status_line) = line.strip_prefix("[GNUPG:] ") else {
+            continue;
+        };
+        let mut fields = status_line.split_whitespace();
+        let keyword = fields.next().unwrap_or_default();
+        let args = fields.collect::<Vec<_>>();
+        let first_arg = args.first().map(|x| x.to_string()).unwrap_or_default();
+
+        let status = match keyword {
+            "VALIDSIG" => {
+                // prefer fingerprint of the primary key, if the signature was made by subkey
+                let fingerprint = args.get(9).or(args.first()).unwrap_or(&"");
+                valid_fingerprint = Some(fingerprint.to_string());
+                continue;
+            },
+            "BADSIG" => SigStatus::Bad { keyid: first_arg },
+            "EXPKEYSIG" => SigStatus::ExpiredKey { keyid: first_arg },
+            "REVKEYSIG" => SigStatus::RevokedKey { keyid: first_arg },
+            "EXPSIG" => SigStatus::ExpiredSig { keyid: first_arg },
+            "NO_PUBKEY" => SigStatus::UnknownKey { keyid: first_arg },
+            // rc 9 means missing public key, which is reported with NO_PUBKEY as well
+            "ERRSIG" if args.get(5) == Some(&"9") => SigStatus::UnknownKey { keyid: first_arg },
+            "ERRSIG" => {
+                SigStatus::Invalid { reason: format!("can't check signature: {status_line}") }
+            },
+            "NODATA" => SigStatus::Invalid { reason: "no valid OpenPGP data found".to_owned() },
+            _ => continue,
+        };
+
+        // keep the most specific failure, e.g BADSIG is reported before the generic ERRSIG
+        if failure.is_none() || matches!(failure, Some(SigStatus::Invalid { .. })) {
+            failure = Some(status);
+        }
+    }
+
+    match (failure, valid_fingerprint) {
+        (Some(failure), _) => failure,
+        (None, Some(fingerprint)) => SigStatus::Valid { fingerprint },
+        (None, None) => {
+            SigStatus::Invalid { reason: "gpgv didn't report valid signature".to_owned() }
+        },
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use crate::sig_utils::*;
+    use crate::utils;
+
+    use std::fs;
+
+    // Runs gpg with the throwaway home dir
+    pub fn run_gpg(gnupg_home: &str, args: &[&str]) -> String {
+        let output = Exec::cmd("gpg")
+            .env("GNUPGHOME", gnupg_home)
+            .args(
+                &[&["--batch", "--yes", "--pinentry-mode", "loopback", "--passphrase", ""], args]
+                    .concat(),
+            )
+            .stdout(Redirection::Pipe)
+            .stderr(Redirection::Merge)
+            .capture()
+            .expect("Failed to run gpg");
+        let proc_output = String::from_utf8_lossy(&output.stdout).to_string();
+        assert!(output.success(), "gpg {args:?} failed:\n{proc_output}");
+        proc_output
+    }
+
+    // Generates throwaway signing key in the new gpg home dir, returns the home dir and
+    // fingerprint of the key
+    pub fn create_test_key(temp_dir: &str, name: &str, extra_args: &[&str]) -> (String, String) {
+        let gnupg_home = format!("{temp_dir}/gnupg-{name}");
+        fs::create_dir_all(&gnupg_home).unwrap();
+        fs::set_permissions(&gnupg_home, std::os::unix::fs::PermissionsExt::from_mode(0o700))
+            .unwrap();
+
+        let uid = format!("{name} <{name}@example.org>");
+        run_gpg(
+            &gnupg_home,
+            &[extra_args, &["--quick-gen-key", &uid, "ed25519", "sign", "1y"]].concat(),
+        );
+
+        let key_listing = run_gpg(&gnupg_home, &["--with-colons", "--list-keys", &uid]);
+        let fingerprint = key_listing
+            .lines()
+            .find_map(|line| line.strip_prefix("fpr:"))
+            .map(|line| line.trim_matches(':').to_owned())
+            .expect("Failed to get fingerprint of test key");
+
+        (gnupg_home, fingerprint)
+    }
+
+    // Exports public keys of the gpg home dir into the keyring file
+    pub fn export_keyring(gnupg_home: &str, keyring_path: &str) {
+        run_gpg(gnupg_home, &["--output", keyring_path, "--export"]);
+    }
+
+    pub fn sign_file(gnupg_home: &str, filepath: &str, extra_args: &[&str]) {
+        let sig_path = format!("{filepath}.sig");
+        run_gpg(
+            gnupg_home,
+            &[extra_args, &["--detach-sign", "--output", &sig_path, filepath]].concat(),
+        );
+    }
+
+    pub fn stop_gpg_agent(gnupg_home: &str) {
+        let _ =
+            Exec::cmd("gpgconf").env("GNUPGHOME", gnupg_home).args(&["--kill", "gpg-agent"]).join();
+    }
+
+    #[test]
+    fn test_parse_gpgv_status() {
+        let status_output = "[GNUPG:] NEWSIG\n[GNUPG:] KEY_CONSIDERED ABCD 0\n[GNUPG:] GOODSIG \
+                             1234 Test <test@example.org>\n[GNUPG:] VALIDSIG SUBKEY 2024-01-01 \
+                             1704067200 0 4 0 22 10 00 PRIMARY\n";
+        assert_eq!(parse_gpgv_status(status_output), SigStatus::Valid {
+            fingerprint: "PRIMARY".to_owned()
+        });
+
+        let status_output = "[GNUPG:] NEWSIG\n[GNUPG:] BADSIG 1234 Test <test@example.org>\n";
+        assert_eq!(parse_gpgv_status(status_output), SigStatus::Bad { keyid: "1234".to_owned() });
+
+        let status_output = "[GNUPG:] NEWSIG\n[GNUPG:] ERRSIG 1234 22 10 00 1704067200 9 \
+                             -\n[GNUPG:] NO_PUBKEY 1234\n";
+        assert_eq!(parse_gpgv_status(status_output), SigStatus::UnknownKey {
+            keyid: "1234".to_owned()
+        });
+
+        let status_output = "[GNUPG:] NEWSIG\n[GNUPG:] EXPKEYSIG 1234 Test \
+                             <test@example.org>\n[GNUPG:] VALIDSIG SUBKEY 2024-01-01 1704067200 0 \
+                             4 0 22 10 00 PRIMARY\n";
+        assert_eq!(parse_gpgv_status(status_output), SigStatus::ExpiredKey {
+            keyid: "1234".to_owned()
+        });
+
+        assert_eq!(parse_gpgv_status("[GNUPG:] NODATA 1\n"), SigStatus::Invalid {
+            reason: "no valid OpenPGP data found".to_owned()
+        });
+        assert!(!parse_gpgv_status("").is_valid());
+    }
+
+    #[test]
+    fn test_verify_pkg_signature() {
+        let temp_dir = utils::create_temporary_directory(None).expect("Failed to create temp dir");
+        let keyring_path = format!("{temp_dir}/trusted.gpg");
+
+        let (gnupg_home, fingerprint) = create_test_key(&temp_dir, "builder", &[]);
+        let (other_gnupg_home, _) = create_test_key(&temp_dir, "stranger", &[]);
+        export_keyring(&gnupg_home, &keyring_path);
+
+        // valid signature
+        let pkgfile = utils::create_test_package(&temp_dir, "dash", "0.5.12-1.1", "x86_64");
+        sign_file(&gnupg_home, &pkgfile, &[]);
+        assert_eq!(verify_pkg_signature(&pkgfile, Some(&keyring_path)), SigStatus::Valid {
+            fingerprint: fingerprint.clone()
+        });
+
+        // modified package
+        let st_pkgfile = utils::create_test_package(&temp_dir, "st", "0.8.4-2", "x86_64");
+        fs::copy(format!("{pkgfile}.sig"), format!("{st_pkgfile}.sig")).unwrap();
+        assert!(matches!(
+            verify_pkg_signature(&st_pkgfile, Some(&keyring_path)),
+            SigStatus::Bad { .. }
+        ));
+
+        // signed by key which is not in the keyring
+        let dwm_pkgfile = utils::create_test_package(&temp_dir, "dwm", "6.2-4", "x86_64");
+        sign_file(&other_gnupg_home, &dwm_pkgfile, &[]);
+        assert!(matches!(
+            verify_pkg_signature(&dwm_pkgfile, Some(&keyring_path)),
+            SigStatus::UnknownKey { .. }
+        ));
+
+        // empty and corrupted signatures
+        let emacs_pkgfile = utils::create_test_package(&temp_dir, "emacs", "29.4-2.1", "x86_64");
+        fs::File::create(format!("{emacs_pkgfile}.sig")).unwrap();
+        assert!(matches!(
+            verify_pkg_signature(&emacs_pkgfile, Some(&keyring_path)),
+            SigStatus::Invalid { .. }
+        ));
+        fs::write(format!("{emacs_pkgfile}.sig"), b"garbage signature").unwrap();
+        assert!(!verify_pkg_signature(&emacs_pkgfile, Some(&keyring_path)).is_valid());
+
+        // missing signature
+        let argon2_pkgfile =
+            utils::create_test_package(&temp_dir, "argon2", "20190702-5.1", "x86_64");
+        assert_eq!(verify_pkg_signature(&argon2_pkgfile, Some(&keyring_path)), SigStatus::Missing);
+
+        stop_gpg_agent(&gnupg_home);
+        stop_gpg_agent(&other_gnupg_home);
+        fs::remove_dir_all(temp_dir).unwrap();
+    }
+
+    #[test]
+    fn test_normalize_fingerprint() {
+        let fingerprint = "3B94A80E50A477C7EC4C1E0D0F3E2D5F7C1A9B3D";
+        assert_eq!(normalize_fingerprint(fingerprint).as_deref(), Some(fingerprint));
+        assert_eq!(
+            normalize_fingerprint("0x3b94 a80e 50a4 77c7 ec4c 1e0d 0f3e 2d5f 7c1a 9b3d").as_deref(),
+            Some(fingerprint)
+        );
+        // short key ids are not accepted
+        assert_eq!(normalize_fingerprint("7C1A9B3D"), None);
+        assert_eq!(normalize_fingerprint("ZZ94A80E50A477C7EC4C1E0D0F3E2D5F7C1A9B3D"), None);
+    }
+
+    #[test]
+    fn test_check_pkg_signer() {
+        let temp_dir = utils::create_temporary_directory(None).expect("Failed to create temp dir");
+        let keyring_path = format!("{temp_dir}/trusted.gpg");
+
+        let (gnupg_home, fingerprint) = create_test_key(&temp_dir, "buildserver", &[]);
+        let (other_gnupg_home, other_fingerprint) = create_test_key(&temp_dir, "developer", &[]);
+        export_keyring(&gnupg_home, &keyring_path);
+        run_gpg(&other_gnupg_home, &["--export", "--output", &format!("{temp_dir}/dev.gpg")]);
+        run_gpg(&gnupg_home, &["--import", &format!("{temp_dir}/dev.gpg")]);
+        export_keyring(&gnupg_home, &keyring_path);
+
+        let pkgfile = utils::create_test_package(&temp_dir, "dash", "0.5.12-1.1", "x86_64");
+        sign_file(&gnupg_home, &pkgfile, &[]);
+        let dev_pkgfile = utils::create_test_package(&temp_dir, "st", "0.8.4-2", "x86_64");
+        sign_file(&other_gnupg_home, &dev_pkgfile, &[]);
+
+        let allowed_signers = [fingerprint.to_lowercase()];
+        assert!(check_pkg_signer(&pkgfile, Some(&keyring_path), &allowed_signers).is_valid());
+        assert_eq!(
+            check_pkg_signer(&dev_pkgfile, Some(&keyring_path), &allowed_signers),
+            SigStatus::UntrustedSigner { fingerprint: other_fingerprint }
+        );
+        // any key from the keyring is accepted without the allowlist
+        assert!(check_pkg_signer(&dev_pkgfile, Some(&keyring_path), &[]).is_valid());
+
+        stop_gpg_agent(&gnupg_home);
+        stop_gpg_agent(&other_gnupg_home);
+        fs::remove_dir_all(temp_dir).unwrap();
+    }
+
+    #[test]
+    fn test_verify_pkg_signature_expired_key() {
+        let temp_dir = utils::create_temporary_directory(None).expect("Failed to create temp dir");
+        let keyring_path = format!("{temp_dir}/trusted.gpg");
+
+        // the key was created and used long time ago, and has expired since then
+        let faked_time = ["--faked-system-time", "20200101T000000!"];
+        let (gnupg_home, _) = create_test_key(&temp_dir, "oldbuilder", &faked_time);
+        export_keyring(&gnupg_home, &keyring_path);
+
+        let pkgfile = utils::create_test_package(&temp_dir, "dash", "0.5.12-1.1", "x86_64");
+        sign_file(&gnupg_home, &pkgfile, &faked_time);
+        assert!(matches!(
+            verify_pkg_signature(&pkgfile, Some(&keyring_path)),
+            SigStatus::ExpiredKey { .. }
+        ));
+
+        stop_gpg_agent(&gnupg_home);
+        fs::remove_dir_all(temp_dir).unwrap();
+    }
+}
@@ -0,0 +1,65 @@
+use std::fmt;
+use std::process::ExitCode;
+
+/// Exit status of the command, see the exit codes in README
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum ExitStatus {
+    /// The command is done, or the repo is up-to-date
+    Success = 0,
+    /// The command failed
+    OperationalError = 1,
+    // 2 is used by clap for the invalid usage
+    /// The repo isn't up-to-date (is-pkgs-up-to-date)
+    DriftDetected = 3,
+    /// Packages were rejected by the validation, e.g with invalid signature
+    ValidationFailed = 4,
+}
+
+impl ExitStatus {
+    // Gets the exit status of the failed command
+    pub fn from_error(err: &anyhow::Error) -> Self {
+        if err.chain().any(|x| x.is::<ValidationError>()) {
+            return Self::ValidationFailed;
+        }
+        Self::OperationalError
+    }
+}
+
+impl From<ExitStatus> for ExitCode {
+    fn from(exit_status: ExitStatus) -> Self {
+        ExitCode::from(exit_status as u8)
+    }
+}
+
+/// Error of the package validation, which has its own exit status
+#[derive(Debug)]
+pub struct ValidationError(pub String);
+
+impl fmt::Display for ValidationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl std::error::Error for ValidationError {}
+
+#[cfg(test)]
+mod tests {
+    use crate::status::*;
+
+    use anyhow::Context;
+
+    #[test]
+    fn test_exit_status_from_error() {
+        let err = anyhow::anyhow!("repo-add failed!");
+        assert_eq!(ExitStatus::from_error(&err), ExitStatus::OperationalError);
+
+        let err = anyhow::anyhow!(ValidationError("Found 'invalid' packages".to_owned()));
+        assert_eq!(ExitStatus::from_error(&err), ExitStatus::ValidationFailed);
+
+        let err: anyhow::Result<()> =
+            Err(ValidationError("Found 'invalid' packages".to_owned()).into());
+        let err = err.context("Failed to sync").unwrap_err();
+        assert_eq!(ExitStatus::from_error(&err), ExitStatus::ValidationFailed);
+    }
+}
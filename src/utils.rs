@@ -42,6 +42,30 @@ pub fn create_temporary_directory(max_tries: Option<u32>) -> Option<String> {
     }
 }
 
+// Creates the minimal package archive with .PKGINFO in the provided dir
+#[cfg(test)]
+pub fn create_test_package(dir: &str, pkgname: &str, pkgver: &str, arch: &str) -> String {
+    let pkg_filepath = format!("{dir}/{pkgname}-{pkgver}-{arch}.pkg.tar.zst");
+    let pkginfo = format!(
+        "pkgname = {pkgname}\npkgbase = {pkgname}\npkgver = {pkgver}\narch = {arch}\nsize = 42\n"
+    );
+    let readme = format!("{pkgname} readme");
+
+    let pkg_file = fs::File::create(&pkg_filepath).unwrap();
+    let mut builder = tar::Builder::new(zstd::Encoder::new(pkg_file, 0).unwrap());
+    for (entry_path, content) in
+        [(".PKGINFO".to_owned(), pkginfo), (format!("usr/share/doc/{pkgname}/README"), readme)]
+    {
+        let mut header = tar::Header::new_gnu();
+        header.set_mode(0o644);
+        header.set_size(content.len() as u64);
+        builder.append_data(&mut header, entry_path, content.as_bytes()).unwrap();
+    }
+    builder.into_inner().unwrap().finish().unwrap();
+
+    pkg_filepath
+}
+
 #[cfg(test)]
 mod tests {
     #[test]